@@ -14,9 +14,21 @@ function kill_process_by_name() {
 # kill any previous instances of the game running
 kill_process_by_name rust_cards_example
 
+# --native: use the websocket server built into the game instead of the
+# controlpad server and node web server
+if [[ "$1" == "--native" ]]; then
+    cargo run &
+    ifconfig | grep 'inet ' | tail -1 | sed 's/.*inet \([^ ]*\).*/http:\/\/\1:3000/' | qrencode -t utf8 -m 2
+    exit 0
+fi
+
+# the game built with the ControlpadServer crate (see ipc/Cargo.toml), which
+# cargo gets from a private repo
+export CARGO_NET_GIT_FETCH_WITH_CLI=true
+
 # don't continue if there's build errors
 set -e
-cargo check
+cargo check --manifest-path ipc/Cargo.toml
 set +e
 
 # start the controlpad server and web server
@@ -24,10 +36,9 @@ cd controlpad_server
 ./start.sh &
 cd ..
 
-# build and run the game (getting ControlpadServer dependency from private repo)
-export CARGO_NET_GIT_FETCH_WITH_CLI=true
+# build and run the game
 $(
-    cargo run 2>/dev/null
+    cargo run --manifest-path ipc/Cargo.toml 2>/dev/null
     cd controlpad_server
     ./start.sh -x
 ) &
@@ -1,22 +1,136 @@
 import { sendControlpadMessage } from "./controlpad.js"
 import { hideJoinBox, showJoinBox } from "./join.js"
-import { hideWaitBox } from "./wait.js"
+import { hideWaitBox, showWaitBox } from "./wait.js"
 
                           
+// protocol version and optional features this controller supports (see
+// protocol.md)
+const PROTOCOL_VERSION = 3;
+const CAPABILITIES = ["json", "table", "patch"];
+
+// messages from the game are either colon-delimited or JSON (JSON messages
+// always start with '{'); either way we turn them into an object
+function parseMessage(msg) {
+    if (msg.startsWith("{")) {
+        return JSON.parse(msg);
+    }
+    var parts = msg.split(":");
+    if (parts[0] == "hello") {
+        return { type: "hello", result: parts[1], version: parseInt(parts[2]) };
+    }
+    if (parts[0] == "accept" || parts[0] == "reject") {
+        return { type: parts[0], id: parseInt(parts[1]), reason: parts[2] };
+    }
+    if (parts[0] == "table") {
+        return {
+            type: "table",
+            center_card: parts[1],
+            deck_size: parseInt(parts[2]),
+            splayed_cards: parts[3] ? parts[3].split(";") : [],
+            players: parts[4] ? parts[4].split(";").map((p) => {
+                var player_parts = p.split(",");
+                return { name: player_parts[0], card_count: parseInt(player_parts[1]) };
+            }) : [],
+        };
+    }
+    if (parts[0] == "patch") {
+        var changes = {};
+        // a changed name could contain ':' so rejoin everything after the base
+        var change_str = parts.slice(3).join(":");
+        if (change_str) {
+            for (var change of change_str.split(";")) {
+                var eq = change.indexOf("=");
+                var value = change.substring(eq + 1);
+                changes[change.substring(0, eq)] = value == "" ? null : value;
+            }
+        }
+        return {
+            type: "patch",
+            seq: parseInt(parts[1]),
+            base: parseInt(parts[2]),
+            changes: changes,
+        };
+    }
+    var parsed = { type: parts[0], state: parts[1] };
+    if (parsed.state == "playing") {
+        parsed.name = parts[2];
+        parsed.left_card = parts[3] || null;
+        parsed.right_card = parts[4] || null;
+        if (parts[5]) {
+            parsed.seq = parseInt(parts[5]);
+        }
+    }
+    return parsed;
+}
+
+// states we've received, by seq, so patches can be applied to whichever state
+// the game last saw us acknowledge
+var RECEIVED_STATES = {};
+var CURRENT_SEQ = 0;
+const KEPT_STATES = 20;
+
+function receiveState(seq, state) {
+    if (seq < CURRENT_SEQ) {
+        return; // superseded already
+    }
+    RECEIVED_STATES[seq] = state;
+    delete RECEIVED_STATES[seq - KEPT_STATES];
+    CURRENT_SEQ = seq;
+    sendControlpadMessage("ack:" + seq);
+    updatePlayingState(state.name, state.left_card || "", state.right_card || "");
+    hideWaitBox();
+    hideJoinBox();
+}
+
+function receivePatch(patch) {
+    var base = RECEIVED_STATES[patch.base];
+    if (base === undefined) {
+        // we missed something; ask for the full state
+        sendControlpadMessage("state-request:" + PROTOCOL_VERSION);
+        return;
+    }
+    receiveState(patch.seq, Object.assign({}, base, patch.changes));
+}
+
 // receive messages
 document.addEventListener("controlpad-message", (event) => {
     var msg = event.detail;
     console.log("recv: " + msg);
-    var parts = msg.split(":");
-    // TODO: should check that parts[0] is 'state'
-    var state = parts[1];
-    var arg1 = parts[2];
-    var arg2 = parts[3];
-    var arg3 = parts[4];
-    if (state == "joining") {
+    var parsed = parseMessage(msg);
+    if (parsed.type == "hello") {
+        handleHello(parsed);
+        return;
+    }
+    if (parsed.type == "table") {
+        showTable(parsed);
+        return;
+    }
+    if (parsed.type == "patch") {
+        receivePatch(parsed);
+        return;
+    }
+    if (parsed.type == "kicked") {
+        document.getElementById("cardDiv").style.display = "none";
+        hideJoinBox();
+        showWaitBox("kicked");
+        return;
+    }
+    if (parsed.type == "accept" || parsed.type == "reject") {
+        receiveActionReply(parsed.id, parsed.type == "accept", parsed.reason);
+        return;
+    }
+    if (parsed.type != "state") {
+        return;
+    }
+    if (parsed.state == "joining") {
         showJoinBox();
-    } else if (state == "playing") {
-        updatePlayingState(arg1, arg2, arg3);
+    } else if (parsed.state == "playing" && parsed.seq !== undefined) {
+        // a full state always replaces whatever we had
+        RECEIVED_STATES = {};
+        CURRENT_SEQ = 0;
+        receiveState(parsed.seq, parsed);
+    } else if (parsed.state == "playing") {
+        updatePlayingState(parsed.name, parsed.left_card || "", parsed.right_card || "");
         hideWaitBox();
         hideJoinBox();
     }
@@ -25,15 +139,77 @@ document.addEventListener("controlpad-message", (event) => {
 
 // must implement this function (called by controlpads.js)
 export function controlpadStart() {
-    // start by getting our current state since it's very possible we're
-    // reconnecting and not just connecting for the first time
-    sendControlpadMessage("state-request");
+    // introduce ourselves so the game knows which version of this code we are
+    sendControlpadMessage("hello:" + PROTOCOL_VERSION + ":" + CAPABILITIES.join(","));
+}
+
+// set while we're reloading because the game told us to, so a reload that
+// gets the same cached code doesn't reload again (and again...)
+const RELOADING_KEY = "reloading-for-game";
+
+function handleHello(hello) {
+    if (hello.result == "reload") {
+        // the game doesn't speak our (stale, probably cached) version of the
+        // protocol anymore so get the latest controller code
+        if (sessionStorage.getItem(RELOADING_KEY) === null) {
+            sessionStorage.setItem(RELOADING_KEY, "1");
+            location.reload();
+        } else {
+            showWaitBox("stale");
+        }
+        return;
+    }
+    sessionStorage.removeItem(RELOADING_KEY);
+    // get our current state since it's very possible we're reconnecting and
+    // not just connecting for the first time
+    sendControlpadMessage("state-request:" + PROTOCOL_VERSION);
 }
 
 // must implement this function (called by controlpads.js)
 // called 30 times per second
 export function controlpadUpdate() {
-    
+    resendPendingActions();
+}
+
+// actions (join, deal, card) we've sent that the game hasn't accepted or
+// rejected yet, by id
+var PENDING_ACTIONS = {};
+// start somewhere random so ids from before a reload aren't reused
+var NEXT_ACTION_ID = Math.floor(Math.random() * 1000000000);
+const ACTION_RETRY_MS = 1000;
+
+// send an action with an id; it's resent until the game replies, then
+// on_reply(accepted, reason) is called
+export function sendAction(msg, on_reply) {
+    var id = NEXT_ACTION_ID++;
+    var msg_with_id = msg + ":" + id;
+    PENDING_ACTIONS[id] = { msg: msg_with_id, sent_at: Date.now(), on_reply: on_reply };
+    sendControlpadMessage(msg_with_id);
+}
+
+function receiveActionReply(id, accepted, reason) {
+    var action = PENDING_ACTIONS[id];
+    if (action === undefined) {
+        return; // a reply to a resend we already heard back about
+    }
+    delete PENDING_ACTIONS[id];
+    if (!accepted) {
+        console.log("action rejected: " + action.msg + " (" + reason + ")");
+    }
+    if (action.on_reply) {
+        action.on_reply(accepted, reason);
+    }
+}
+
+function resendPendingActions() {
+    var now = Date.now();
+    for (var id in PENDING_ACTIONS) {
+        var action = PENDING_ACTIONS[id];
+        if (now - action.sent_at > ACTION_RETRY_MS) {
+            action.sent_at = now;
+            sendControlpadMessage(action.msg);
+        }
+    }
 }
 
 var PLAYER_NAME = "";
@@ -82,7 +258,7 @@ function createDealButton() {
     img.src = "./resources/deal.png";
     // todo other things below
     img.addEventListener("click", function() {
-        sendControlpadMessage("deal");
+        sendAction("deal");
     });
     // position the card
     img.style.position = "absolute";
@@ -108,13 +284,22 @@ function createCardElement(side, card_str) {
         // send card:* message on press
         let isLeft = side == "L";
         img.addEventListener("click", () => {
-            sendControlpadMessage("card:" + side + "," + suit + "," + rank);
-            if (isLeft) {
-                LEFT_CARD_STR = "";
-            } else {
-                RIGHT_CARD_STR = "";
-            };
-            showPlayingState();
+            // dim the card until the game tells us whether the play went
+            // through (ignore taps while we're waiting)
+            if (img.style.opacity == "0.5") {
+                return;
+            }
+            img.style.opacity = "0.5";
+            sendAction("card:" + side + "," + suit + "," + rank, (accepted) => {
+                if (accepted) {
+                    if (isLeft) {
+                        LEFT_CARD_STR = "";
+                    } else {
+                        RIGHT_CARD_STR = "";
+                    };
+                }
+                showPlayingState();
+            });
         });
 
     }
@@ -134,3 +319,16 @@ function showName(name) {
     name_line.textContent = name;
     name_box.style.display = "block";
 }
+
+// public state of the table (center card and everyone's card counts)
+function showTable(table) {
+    let table_box = document.getElementById("tableBox");
+    let center_img = document.getElementById("tableCenterCard");
+    let parts = table.center_card.split(",");
+    center_img.src = "./resources/card_fronts/card_" + parts[0] + "_" + parts[1] + ".png";
+    let players_line = document.getElementById("tablePlayersLine");
+    players_line.textContent = table.players
+        .map((p) => p.name + " (" + p.card_count + ")")
+        .join(", ") + " | deck: " + table.deck_size;
+    table_box.style.display = "block";
+}
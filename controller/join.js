@@ -1,4 +1,4 @@
-import { sendControlpadMessage } from "./controlpad.js";
+import { sendAction } from "./app.js";
 
 document.addEventListener('DOMContentLoaded', (event) => {
     document.getElementById('submitJoinButton').addEventListener('click', showJoinCustomConfirm);
@@ -38,8 +38,9 @@ export function showName(name) {
 }
 
 export function confirmJoinSubmit() {
-    let enteredText = document.getElementById("joinInput").value;
-    sendControlpadMessage("join:" + enteredText);
+    // (the game drops these too, they separate fields in messages)
+    let enteredText = document.getElementById("joinInput").value.replace(/[:,;]/g, "");
+    sendAction("join:" + enteredText);
     document.getElementById("joinInput").value = "";
     const join_line = document.getElementById("joinLine");
     join_line.textContent = "";
@@ -12,6 +12,10 @@ export function showWaitBox(wait_type) {
         document.getElementById("waitingLine").innerText = "Waiting for the host to start the game...";
     } else if (wait_type == "round") {
         document.getElementById("waitingLine").innerText = "Waiting for other players to finish up...";
+    } else if (wait_type == "stale") {
+        document.getElementById("waitingLine").innerText = "This controller is out of date, clear your browser's cache and reload the page";
+    } else if (wait_type == "kicked") {
+        document.getElementById("waitingLine").innerText = "You were removed from the game for sending too many messages (reload the page to rejoin)";
     } else if (wait_type == "other-player") {
         document.getElementById("waitingLine").innerText = "Waiting for your next prompt from another player...";
     } else {
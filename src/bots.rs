@@ -0,0 +1,275 @@
+use rand::Rng;
+use rand::thread_rng;
+
+use crate::protocol::PROTOCOL_VERSION;
+use crate::standard_deck::*;
+use crate::transport::local;
+
+// Bots are fake controlpads for testing multiplayer flow without opening a
+// browser tab per player. Each bot connects as a local client, speaks the
+// same (colon-delimited) protocol as controller/app.js, and picks its actions
+// with a BotStrategy.
+
+// bots wait a random number of ticks in this range between actions
+const MIN_THINK_TICKS: u32 = 40;
+const MAX_THINK_TICKS: u32 = 120;
+
+
+//////// BotView ////////
+// what a bot knows about the game (from the messages it's been sent)
+pub struct BotView {
+    pub left_card: Option<CardSpec>,
+    pub right_card: Option<CardSpec>,
+    pub center_card: Option<CardSpec>,
+    pub deck_size: usize,
+}
+
+impl BotView {
+    fn new() -> Self {
+        Self {
+            left_card: None,
+            right_card: None,
+            center_card: None,
+            deck_size: 0,
+        }
+    }
+
+    // the bot's cards along with whether they're on the left
+    pub fn cards(&self) -> Vec<(bool, CardSpec)> {
+        let left = self.left_card.map(|x| (true, x));
+        let right = self.right_card.map(|x| (false, x));
+        left.into_iter().chain(right).collect()
+    }
+}
+
+fn parse_card(card_str: &str) -> Option<CardSpec> {
+    let mut parts = card_str.split(",");
+    CardSpec::try_from_strs(parts.next()?, parts.next()?)
+}
+
+
+//////// BotAction ////////
+#[derive(Clone, Copy)]
+pub enum BotAction {
+    Deal,
+    // play the left (true) or right (false) card
+    Play(bool),
+}
+
+impl BotAction {
+    fn to_message(self, view: &BotView) -> Option<String> {
+        match self {
+            BotAction::Deal => Some("deal".to_string()),
+            BotAction::Play(is_left) => {
+                let (side, card) = if is_left {
+                    ("L", view.left_card?)
+                } else {
+                    ("R", view.right_card?)
+                };
+                Some(format!("card:{},{}", side, card))
+            }
+        }
+    }
+}
+
+
+//////// BotStrategy ////////
+pub trait BotStrategy {
+    // called whenever the bot is ready to act; None to do nothing this time
+    fn choose(&mut self, view: &BotView) -> Option<BotAction>;
+}
+
+// "random", "greedy" or "scripted:<step>,<step>,..." (see ScriptedStrategy)
+pub fn strategy_from_str(s: &str) -> Option<Box<dyn BotStrategy>> {
+    let mut parts = s.splitn(2, ":");
+    match parts.next()? {
+        "random" => Some(Box::new(RandomStrategy)),
+        "greedy" => Some(Box::new(GreedyStrategy)),
+        "scripted" => Some(Box::new(ScriptedStrategy::from_str(parts.next().unwrap_or(""))?)),
+        _ => None,
+    }
+}
+
+// deals or plays a card at random
+pub struct RandomStrategy;
+
+impl BotStrategy for RandomStrategy {
+    fn choose(&mut self, view: &BotView) -> Option<BotAction> {
+        let mut rng = thread_rng();
+        let cards = view.cards();
+        if cards.is_empty() || rng.gen_bool(0.5) {
+            Some(BotAction::Deal)
+        } else {
+            let (is_left, _) = cards[rng.gen_range(0..cards.len())];
+            Some(BotAction::Play(is_left))
+        }
+    }
+}
+
+// plays its highest card if it beats the center card, otherwise deals
+pub struct GreedyStrategy;
+
+impl BotStrategy for GreedyStrategy {
+    fn choose(&mut self, view: &BotView) -> Option<BotAction> {
+        let center_value = view.center_card.map_or(0, |x| x.rank().value());
+        let best = view.cards().into_iter().max_by_key(|(_, card)| card.rank().value());
+        match best {
+            Some((is_left, card)) if card.rank().value() > center_value => {
+                Some(BotAction::Play(is_left))
+            }
+            _ if view.deck_size > 0 => Some(BotAction::Deal),
+            _ => None,
+        }
+    }
+}
+
+// repeats a fixed list of steps: deal, left, right or pass (do nothing)
+pub struct ScriptedStrategy {
+    steps: Vec<Option<BotAction>>,
+    next_step: usize,
+}
+
+impl ScriptedStrategy {
+    pub fn from_str(script: &str) -> Option<Self> {
+        let mut steps = Vec::new();
+        for step in script.split(",") {
+            steps.push(match step.trim() {
+                "deal" => Some(BotAction::Deal),
+                "left" => Some(BotAction::Play(true)),
+                "right" => Some(BotAction::Play(false)),
+                "pass" => None,
+                _ => return None,
+            });
+        }
+        Some(Self { steps, next_step: 0 })
+    }
+}
+
+impl BotStrategy for ScriptedStrategy {
+    fn choose(&mut self, _view: &BotView) -> Option<BotAction> {
+        let step = self.steps[self.next_step];
+        self.next_step = (self.next_step + 1) % self.steps.len();
+        step
+    }
+}
+
+
+//////// Bot ////////
+struct Bot {
+    handle: String,
+    name: String,
+    strategy: Box<dyn BotStrategy>,
+    view: BotView,
+    // whether the game has told us we're playing
+    joined: bool,
+    // id of the action we're waiting to hear back about
+    pending_action: Option<u32>,
+    next_action_id: u32,
+    ticks_until_action: u32,
+    kicked: bool,
+}
+
+impl Bot {
+    fn send(&self, msg: &str) {
+        local::send(&self.handle, msg);
+    }
+
+    fn send_action(&mut self, msg: &str) {
+        let id = self.next_action_id;
+        self.next_action_id += 1;
+        self.pending_action = Some(id);
+        self.send(&format!("{}:{}", msg, id));
+    }
+
+    fn handle_message(&mut self, msg: &str) {
+        let parts: Vec<&str> = msg.split(":").collect();
+        match parts[..] {
+            ["hello", "ok", ..] => self.send(&format!("state-request:{}", PROTOCOL_VERSION)),
+            ["hello", "reload", ..] => println!("WARNING: game told bot {} to reload", &self.name),
+            ["state", "joining"] => {
+                let join = format!("join:{}", &self.name);
+                self.send_action(&join);
+            }
+            ["state", "playing", _name, left, right, ..] => {
+                self.joined = true;
+                self.view.left_card = parse_card(left);
+                self.view.right_card = parse_card(right);
+            }
+            ["table", center, deck_size, ..] => {
+                self.view.center_card = parse_card(center);
+                self.view.deck_size = deck_size.parse().unwrap_or(0);
+            }
+            ["accept", id] | ["reject", id, ..] if id.parse().ok() == self.pending_action => {
+                self.pending_action = None;
+            }
+            ["kicked"] => self.kicked = true,
+            _ => (),
+        }
+    }
+
+    fn update(&mut self) {
+        for msg in local::receive(&self.handle) {
+            self.handle_message(&msg);
+        }
+        if !self.joined || self.kicked || self.pending_action.is_some() {
+            return;
+        }
+        if self.ticks_until_action > 0 {
+            self.ticks_until_action -= 1;
+            return;
+        }
+        self.ticks_until_action = thread_rng().gen_range(MIN_THINK_TICKS..=MAX_THINK_TICKS);
+        let msg = self.strategy.choose(&self.view).and_then(|x| x.to_message(&self.view));
+        if let Some(msg) = msg {
+            self.send_action(&msg);
+        }
+    }
+}
+
+
+//////// BotManager ////////
+pub struct BotManager {
+    bots: Vec<Bot>,
+    bots_spawned: u32,
+}
+
+impl BotManager {
+    pub fn new() -> Self {
+        Self {
+            bots: Vec::new(),
+            bots_spawned: 0,
+        }
+    }
+
+    pub fn spawn(&mut self, strategy: Box<dyn BotStrategy>) {
+        self.bots_spawned += 1;
+        let handle = format!("bot-{}", self.bots_spawned);
+        local::connect(&handle);
+        let bot = Bot {
+            name: format!("Bot {}", self.bots_spawned),
+            handle,
+            strategy,
+            view: BotView::new(),
+            joined: false,
+            pending_action: None,
+            next_action_id: 1,
+            ticks_until_action: MIN_THINK_TICKS,
+            kicked: false,
+        };
+        // the same greeting controller/app.js sends
+        bot.send(&format!("hello:{}:table", PROTOCOL_VERSION));
+        self.bots.push(bot);
+    }
+
+    // call once per tick
+    pub fn update(&mut self) {
+        for bot in &mut self.bots {
+            bot.update();
+        }
+        // kicked bots hang up
+        for bot in self.bots.iter().filter(|x| x.kicked) {
+            local::disconnect(&bot.handle);
+        }
+        self.bots.retain(|x| !x.kicked);
+    }
+}
@@ -0,0 +1,103 @@
+use ggez::{
+    graphics::{Canvas, DrawParam, Image, Mesh, MeshData, Rect, Vertex}, Context,
+};
+use glam::Vec2;
+
+// Draws a card turning over in 3D: the card is split into a grid of small
+// quads that are each put in perspective (the edge turning towards the viewer
+// gets bigger and the edge turning away gets smaller), and the card darkens
+// as it turns edge on.
+// - the axis can point any way (Vec2::Y turns the card over sideways, Vec2::X
+//   turns it over top to bottom)
+// - past a quarter turn the other side shows (e.g. the back of a card turning
+//   face up)
+
+
+// how far the viewer's eye is from the table (in pixels); closer makes the
+// perspective stronger
+const VIEW_DISTANCE: f32 = 900.0;
+// brightness of a card that's edge on (1.0 when it's flat)
+const EDGE_ON_BRIGHTNESS: f32 = 0.35;
+// the card is split into GRID_SIZE x GRID_SIZE quads (more is smoother but
+// makes a bigger mesh)
+const GRID_SIZE: u32 = 6;
+
+
+//////// CardFlip ////////
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CardFlip {
+    // how far it's turned (in radians, 0.0 is flat showing the first side and
+    // PI is flat showing the other side)
+    pub angle: f32,
+    // the line through the card's center it turns around
+    pub axis: Vec2,
+}
+
+impl CardFlip {
+    pub fn new(angle: f32, axis: Vec2) -> Self {
+        Self { angle, axis: axis.normalize_or_zero() }
+    }
+
+    pub fn is_flat(&self) -> bool {
+        self.angle == 0.0
+    }
+
+    pub fn shows_first_side(&self) -> bool {
+        self.angle.cos() >= 0.0
+    }
+
+    fn brightness(&self) -> f32 {
+        EDGE_ON_BRIGHTNESS + (1.0 - EDGE_ON_BRIGHTNESS) * self.angle.cos().abs()
+    }
+
+    // where the point local_loc (relative to the card's center) ends up
+    fn project(&self, local_loc: Vec2) -> Vec2 {
+        let normal = self.axis.perp();
+        let along = local_loc.dot(self.axis);
+        let across = local_loc.dot(normal);
+        // (depth is positive going away from the viewer)
+        let depth = across * self.angle.sin();
+        let perspective = VIEW_DISTANCE / (VIEW_DISTANCE + depth);
+        (along * self.axis + across * self.angle.cos() * normal) * perspective
+    }
+}
+
+// draws the part of image inside first_src (or second_src once it's turned
+// over) size big, transformed by param, then flipped
+// - srcs are (first_src, second_src), as fractions of image's size (like
+//   DrawParam::src), and size is how big the card is before param's scale
+//   (whatever size the src is in image)
+// - param's offset and src are ignored
+pub fn draw_card_flip(canvas: &mut Canvas, ctx: &mut Context, image: &Image, srcs: (Rect, Rect),
+                      size: Vec2, flip: CardFlip, param: DrawParam) {
+    let (first_src, second_src) = srcs;
+    let src = if flip.shows_first_side() { first_src } else { second_src };
+    let brightness = flip.brightness();
+    let color = [brightness, brightness, brightness, 1.0];
+    let mut vertices = Vec::new();
+    for row in 0..=GRID_SIZE {
+        for column in 0..=GRID_SIZE {
+            let grid_loc = Vec2::new(column as f32, row as f32) / GRID_SIZE as f32;
+            let mut local_loc = (grid_loc - 0.5) * size;
+            // the other side is mirrored across the axis (so it isn't drawn
+            // backwards once it's turned over)
+            if !flip.shows_first_side() {
+                let normal = flip.axis.perp();
+                local_loc -= 2.0 * local_loc.dot(normal) * normal;
+            }
+            let position = 0.5 * size + flip.project(local_loc);
+            let uv = Vec2::new(src.x, src.y) + grid_loc * Vec2::new(src.w, src.h);
+            vertices.push(Vertex { position: position.into(), uv: uv.into(), color });
+        }
+    }
+    let mut indices = Vec::new();
+    let row_len = GRID_SIZE + 1;
+    for row in 0..GRID_SIZE {
+        for column in 0..GRID_SIZE {
+            let i = row * row_len + column;
+            indices.extend([i, i + 1, i + row_len, i + 1, i + row_len + 1, i + row_len]);
+        }
+    }
+    let mesh = Mesh::from_data(ctx, MeshData { vertices: &vertices, indices: &indices });
+    canvas.draw_textured_mesh(mesh, image.clone(), param);
+}
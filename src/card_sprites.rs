@@ -0,0 +1,128 @@
+use ggez::{
+    graphics::{Canvas, Color, DrawParam, InstanceArray}, Context,
+};
+use glam::{Mat2, Vec2};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::card_flip::*;
+use crate::standard_deck::{CardAtlas, CardFace};
+
+// Cards to draw this frame, each with its own transform (position, scale,
+// rotation, flip, opacity) and z index. Nothing is drawn until draw() is called,
+// which draws everything from the lowest z to the highest so which card ends up
+// on top doesn't depend on the order the drawing code runs in.
+// - every card image is in one atlas (see CardAtlas in standard_deck.rs), so
+//   runs of flat cards next to each other in z order (e.g. the whole deck
+//   stack or the whole splayed row) are drawn as one batch with an
+//   InstanceArray; a card that's being flipped is drawn on its own
+
+
+//////// CardSprite ////////
+pub struct CardSprite {
+    pub face: CardFace,
+    // (shown once it's flipped over)
+    pub other_side: CardFace,
+    // top left corner (before it's scaled or rotated)
+    pub loc: Vec2,
+    // higher is drawn on top (sprites with the same z are drawn in the order
+    // they were added)
+    pub z: i32,
+    pub scale: Vec2,
+    // (in radians, around the card's center)
+    pub rotation: f32,
+    pub flip: CardFlip,
+    pub opacity: f32,
+}
+
+impl CardSprite {
+    pub fn new(face: CardFace, loc: Vec2, z: i32) -> Self {
+        Self {
+            face,
+            other_side: face,
+            loc,
+            z,
+            scale: Vec2::ONE,
+            rotation: 0.0,
+            flip: CardFlip::new(0.0, Vec2::Y),
+            opacity: 1.0,
+        }
+    }
+
+    // draws something image_scale times card_size big (before the sprite's
+    // own scale) where the sprite goes
+    fn draw_param(&self, atlas: &CardAtlas, image_scale: Vec2) -> DrawParam {
+        // canvas rotations turn around the card's top left corner, so move the
+        // corner to where it ends up when the card turns around its center
+        let half_size = 0.5 * atlas.card_size();
+        let center = self.loc + half_size;
+        let dest = center - Mat2::from_angle(self.rotation) * (half_size * self.scale);
+        DrawParam::default()
+            .dest(dest)
+            .scale(self.scale * image_scale)
+            .rotation(self.rotation)
+            .color(Color::new(1.0, 1.0, 1.0, self.opacity))
+    }
+}
+
+// a small random-looking nudge for a sprite: (offset, rotation) each between
+// -max and max; the same seed always gets the same nudge so a card doesn't
+// jump around from frame to frame
+pub fn jitter(seed: impl Hash, max_offset: f32, max_rotation: f32) -> (Vec2, f32) {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    let bits = hasher.finish();
+    // three numbers from -1.0 to 1.0 out of the hash's bits
+    let unit = |shift: u32| ((bits >> shift) & 0xffff) as f32 / 0xffff as f32 * 2.0 - 1.0;
+    (Vec2::new(unit(0), unit(16)) * max_offset, unit(32) * max_rotation)
+}
+
+
+//////// SpriteLayer ////////
+pub struct SpriteLayer {
+    sprites: Vec<CardSprite>,
+}
+
+impl SpriteLayer {
+    pub fn new() -> Self {
+        Self { sprites: Vec::new() }
+    }
+
+    pub fn add(&mut self, sprite: CardSprite) {
+        self.sprites.push(sprite);
+    }
+
+    // (leaves canvas drawing with the atlas's sampler)
+    pub fn draw(mut self, canvas: &mut Canvas, ctx: &mut Context, atlas: &CardAtlas) {
+        // (a stable sort, so equal z's stay in the order they were added)
+        self.sprites.sort_by_key(|x| x.z);
+        canvas.set_sampler(atlas.sampler());
+        let mut batch = Vec::new();
+        for sprite in &self.sprites {
+            if sprite.flip.is_flat() {
+                // (atlas images are drawn at their own size unless they're scaled)
+                batch.push(sprite.draw_param(atlas, atlas.image_scale()).src(atlas.src(sprite.face)));
+                continue;
+            }
+            // (the batch has to be drawn first so it stays underneath)
+            draw_batch(canvas, ctx, atlas, &mut batch);
+            // (flipping cards are meshes that are already card_size big)
+            let srcs = (atlas.src(sprite.face), atlas.src(sprite.other_side));
+            draw_card_flip(canvas, ctx, atlas.image(), srcs, atlas.card_size(),
+                           sprite.flip, sprite.draw_param(atlas, Vec2::ONE));
+        }
+        draw_batch(canvas, ctx, atlas, &mut batch);
+    }
+}
+
+// draws every card in batch with one draw call and empties it
+fn draw_batch(canvas: &mut Canvas, ctx: &mut Context, atlas: &CardAtlas, batch: &mut Vec<DrawParam>) {
+    if batch.is_empty() {
+        return;
+    }
+    // (a new InstanceArray each time since the canvas doesn't draw it until
+    // it's finished, so reusing one would draw the last batch over and over)
+    let mut instances = InstanceArray::new(ctx, atlas.image().clone());
+    instances.set(batch.drain(..));
+    canvas.draw(&instances, DrawParam::default());
+}
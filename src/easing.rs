@@ -0,0 +1,285 @@
+// (not every curve is used by the game, they're here for whatever needs them)
+#![allow(dead_code)]
+
+use ggez::graphics::Color;
+use glam::Vec2;
+use std::f32::consts::PI;
+
+// Easing curves and interpolation: Interpolation maps linear progress (0.0 to
+// 1.0) to eased progress, and the functions below use it to interpolate
+// numbers, points, colors and rotations or to move along curved paths.
+// - the standard curves (cubic, quart, back, elastic, bounce) are Robert
+//   Penner's; see https://easings.net for what they look like
+// - back and elastic overshoot (they go past 1.0 before settling) and bounce
+//   goes back and forth, every other curve only ever moves forward
+
+
+//////// Interpolation ////////
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interpolation {
+    Linear,
+    SlowDown,
+    SpeedUp,
+    RoundStart,
+    RoundEnd,
+    RoundFull,
+    Natural,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    QuartIn,
+    QuartOut,
+    QuartInOut,
+    BackIn,
+    BackOut,
+    BackInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+}
+
+// how far back and elastic curves overshoot
+const BACK_OVERSHOOT: f32 = 1.70158;
+const BACK_IN_OUT_OVERSHOOT: f32 = BACK_OVERSHOOT * 1.525;
+const ELASTIC_PERIOD: f32 = 2.0 * PI / 3.0;
+const ELASTIC_IN_OUT_PERIOD: f32 = 2.0 * PI / 4.5;
+
+impl Interpolation {
+    // eased progress for progress (both 0.0 to 1.0)
+    pub fn ease(self, progress: f32) -> f32 {
+        use Interpolation::*;
+        let p = progress;
+        match self {
+            Linear => p,
+            SlowDown => p.sqrt(),
+            SpeedUp => p.powf(2.0),
+            RoundStart => 1.0 - (p * PI/2.0).cos(),
+            RoundEnd => (p * PI/2.0).sin(),
+            RoundFull => (1.0 + ((1.0 - p) * PI).cos())/2.0,
+            Natural => (RoundFull.ease(p) + RoundEnd.ease(p))/2.0,
+            CubicIn => p.powi(3),
+            CubicOut => 1.0 - (1.0 - p).powi(3),
+            CubicInOut => {
+                if p < 0.5 { 4.0 * p.powi(3) } else { 1.0 - (2.0 - 2.0*p).powi(3)/2.0 }
+            }
+            QuartIn => p.powi(4),
+            QuartOut => 1.0 - (1.0 - p).powi(4),
+            QuartInOut => {
+                if p < 0.5 { 8.0 * p.powi(4) } else { 1.0 - (2.0 - 2.0*p).powi(4)/2.0 }
+            }
+            BackIn => {
+                let c = BACK_OVERSHOOT;
+                (c + 1.0) * p.powi(3) - c * p.powi(2)
+            }
+            BackOut => 1.0 - BackIn.ease(1.0 - p),
+            BackInOut => {
+                let c = BACK_IN_OUT_OVERSHOOT;
+                if p < 0.5 {
+                    (2.0*p).powi(2) * ((c + 1.0) * 2.0*p - c) / 2.0
+                } else {
+                    ((2.0*p - 2.0).powi(2) * ((c + 1.0) * (2.0*p - 2.0) + c) + 2.0) / 2.0
+                }
+            }
+            // (exactly 0.0 and 1.0 at the ends rather than very nearly)
+            ElasticIn | ElasticOut | ElasticInOut if p <= 0.0 => 0.0,
+            ElasticIn | ElasticOut | ElasticInOut if p >= 1.0 => 1.0,
+            ElasticIn => -(2.0f32).powf(10.0*p - 10.0) * ((10.0*p - 10.75) * ELASTIC_PERIOD).sin(),
+            ElasticOut => 1.0 - ElasticIn.ease(1.0 - p),
+            ElasticInOut => {
+                let wave = ((20.0*p - 11.125) * ELASTIC_IN_OUT_PERIOD).sin();
+                if p < 0.5 {
+                    -(2.0f32).powf(20.0*p - 10.0) * wave / 2.0
+                } else {
+                    (2.0f32).powf(10.0 - 20.0*p) * wave / 2.0 + 1.0
+                }
+            }
+            BounceIn => 1.0 - BounceOut.ease(1.0 - p),
+            BounceOut => bounce_out(p),
+            BounceInOut => {
+                if p < 0.5 { (1.0 - bounce_out(1.0 - 2.0*p))/2.0 } else { (1.0 + bounce_out(2.0*p - 1.0))/2.0 }
+            }
+        }
+    }
+}
+
+// four bounces, each smaller than the last
+fn bounce_out(p: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if p < 1.0/D {
+        N * p * p
+    } else if p < 2.0/D {
+        let p = p - 1.5/D;
+        N * p * p + 0.75
+    } else if p < 2.5/D {
+        let p = p - 2.25/D;
+        N * p * p + 0.9375
+    } else {
+        let p = p - 2.625/D;
+        N * p * p + 0.984375
+    }
+}
+
+
+//////// Interpolating values ////////
+// (p is eased progress, so it can go a little past 0.0 or 1.0)
+pub fn lerp(start: f32, end: f32, p: f32) -> f32 {
+    start + (end - start) * p
+}
+
+pub fn lerp_color(start: Color, end: Color, p: f32) -> Color {
+    Color::new(
+        lerp(start.r, end.r, p),
+        lerp(start.g, end.g, p),
+        lerp(start.b, end.b, p),
+        lerp(start.a, end.a, p),
+    )
+}
+
+// rotates the short way round (angles are in radians)
+pub fn lerp_rotation(start: f32, end: f32, p: f32) -> f32 {
+    let turn = (end - start + PI).rem_euclid(2.0 * PI) - PI;
+    start + turn * p
+}
+
+// interpolation between two points (use Interpolation::Linear for constant speed)
+pub fn interpolate(start_loc: Vec2, end_loc: Vec2, interp: Interpolation, progress: f32) -> Vec2 {
+    let p = interp.ease(progress);
+    (1.0 - p) * start_loc  +  p * end_loc
+}
+
+// interpolate along a curved path between start_loc and end_loc that curves
+// towards intermediary (the final interpolation is linear, change the function
+// if you want to do something different)
+pub fn interpolate2(start_loc: Vec2, intermediary_loc: Vec2, end_loc: Vec2,
+                    interp1: Interpolation, interp2: Interpolation, progress: f32) -> Vec2 {
+    let interp_a = interpolate(start_loc, intermediary_loc, interp1, progress);
+    let interp_b = interpolate(intermediary_loc, end_loc, interp2, progress);
+    interpolate(interp_a, interp_b, Interpolation::Linear, progress)
+}
+
+
+//////// Paths ////////
+// a point on the cubic Bézier curve from start to end (the path heads towards
+// control1 as it leaves start and comes in from control2's direction)
+pub fn cubic_bezier(start: Vec2, control1: Vec2, control2: Vec2, end: Vec2, t: f32) -> Vec2 {
+    let u = 1.0 - t;
+    u*u*u * start + 3.0*u*u*t * control1 + 3.0*u*t*t * control2 + t*t*t * end
+}
+
+// a point on a smooth path through all of points (t from 0.0 at the first
+// point to 1.0 at the last, each stretch between points taking an equal share)
+pub fn catmull_rom(points: &[Vec2], t: f32) -> Vec2 {
+    match points.len() {
+        0 => return Vec2::ZERO,
+        1 => return points[0],
+        _ => (),
+    }
+    let stretches = points.len() - 1;
+    let at = t.clamp(0.0, 1.0) * stretches as f32;
+    let i = (at as usize).min(stretches - 1);
+    let t = at - i as f32;
+    // (the ends are repeated so the path starts and ends on them)
+    let p0 = points[i.saturating_sub(1)];
+    let p1 = points[i];
+    let p2 = points[i + 1];
+    let p3 = points[(i + 2).min(stretches)];
+    0.5 * (2.0 * p1
+           + (p2 - p0) * t
+           + (2.0*p0 - 5.0*p1 + 4.0*p2 - p3) * t*t
+           + (3.0*p1 - p0 - 3.0*p2 + p3) * t*t*t)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Interpolation::*;
+
+    const ALL: [Interpolation; 22] = [
+        Linear, SlowDown, SpeedUp, RoundStart, RoundEnd, RoundFull, Natural,
+        CubicIn, CubicOut, CubicInOut, QuartIn, QuartOut, QuartInOut,
+        BackIn, BackOut, BackInOut, ElasticIn, ElasticOut, ElasticInOut,
+        BounceIn, BounceOut, BounceInOut,
+    ];
+    // the curves that never go backwards
+    const MONOTONIC: [Interpolation; 13] = [
+        Linear, SlowDown, SpeedUp, RoundStart, RoundEnd, RoundFull, Natural,
+        CubicIn, CubicOut, CubicInOut, QuartIn, QuartOut, QuartInOut,
+    ];
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn curves_start_at_0_and_end_at_1() {
+        for interp in ALL {
+            assert!(close(interp.ease(0.0), 0.0), "{:?} starts at {}", interp, interp.ease(0.0));
+            assert!(close(interp.ease(1.0), 1.0), "{:?} ends at {}", interp, interp.ease(1.0));
+        }
+    }
+
+    #[test]
+    fn curves_are_monotonic() {
+        for interp in MONOTONIC {
+            let mut last = interp.ease(0.0);
+            for i in 1..=1000 {
+                let p = interp.ease(i as f32 / 1000.0);
+                assert!(p >= last - 1e-6, "{:?} goes backwards at {}", interp, i as f32 / 1000.0);
+                last = p;
+            }
+        }
+    }
+
+    #[test]
+    fn in_out_curves_are_halfway_at_half() {
+        for interp in [CubicInOut, QuartInOut, BackInOut, ElasticInOut, BounceInOut] {
+            assert!(close(interp.ease(0.5), 0.5), "{:?} is at {}", interp, interp.ease(0.5));
+        }
+    }
+
+    #[test]
+    fn lerps_hit_their_ends() {
+        assert!(close(lerp(3.0, 7.0, 0.0), 3.0));
+        assert!(close(lerp(3.0, 7.0, 1.0), 7.0));
+        let start = Color::new(0.0, 0.2, 0.4, 1.0);
+        let end = Color::new(1.0, 0.6, 0.0, 0.0);
+        assert_eq!(lerp_color(start, end, 0.0), start);
+        assert_eq!(lerp_color(start, end, 1.0), end);
+        let start_loc = Vec2::new(1.0, 2.0);
+        let end_loc = Vec2::new(-5.0, 8.0);
+        for interp in ALL {
+            assert!(interpolate(start_loc, end_loc, interp, 0.0).distance(start_loc) < 1e-4);
+            assert!(interpolate(start_loc, end_loc, interp, 1.0).distance(end_loc) < 1e-4);
+        }
+    }
+
+    #[test]
+    fn rotations_go_the_short_way() {
+        // from just below a full turn to just above zero is a small step forward
+        let start = 2.0 * PI - 0.1;
+        let end = 0.1;
+        assert!(close(lerp_rotation(start, end, 0.5), 2.0 * PI));
+        assert!(close(lerp_rotation(start, end, 1.0), 2.0 * PI + 0.1));
+        assert!(close(lerp_rotation(0.0, -1.0, 1.0), -1.0));
+    }
+
+    #[test]
+    fn paths_hit_their_points() {
+        let a = Vec2::new(0.0, 0.0);
+        let b = Vec2::new(10.0, 30.0);
+        let c = Vec2::new(40.0, -20.0);
+        let d = Vec2::new(60.0, 5.0);
+        assert!(cubic_bezier(a, b, c, d, 0.0).distance(a) < 1e-4);
+        assert!(cubic_bezier(a, b, c, d, 1.0).distance(d) < 1e-4);
+        let points = [a, b, c, d];
+        for (i, point) in points.iter().enumerate() {
+            let t = i as f32 / 3.0;
+            assert!(catmull_rom(&points, t).distance(*point) < 1e-3, "missed point {}", i);
+        }
+    }
+}
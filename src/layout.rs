@@ -0,0 +1,149 @@
+use ggez::graphics::{Canvas, Rect};
+use glam::Vec2;
+
+// Everything on the table is laid out on a virtual screen VIRTUAL_WIDTH x
+// VIRTUAL_HEIGHT (virtual pixels) which is scaled up or down to fit the window,
+// so the table looks the same at any resolution. If the window's shape is
+// different from the virtual screen's, the virtual screen is centered with the
+// table color around it.
+// - where things go is given by a Placement: a point anchored to one of the
+//   virtual screen's corners, edges or center, moved by a fraction of the
+//   virtual screen's size and/or some virtual pixels
+
+
+pub const VIRTUAL_WIDTH: f32 = 1920.0;
+pub const VIRTUAL_HEIGHT: f32 = 1080.0;
+
+
+//////// Anchor ////////
+// (the table only uses a few of these so far)
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    // where the anchor is as a fraction of the virtual screen's size
+    fn fraction(&self) -> Vec2 {
+        use Anchor::*;
+        match self {
+            TopLeft => Vec2::new(0.0, 0.0),
+            Top => Vec2::new(0.5, 0.0),
+            TopRight => Vec2::new(1.0, 0.0),
+            Left => Vec2::new(0.0, 0.5),
+            Center => Vec2::new(0.5, 0.5),
+            Right => Vec2::new(1.0, 0.5),
+            BottomLeft => Vec2::new(0.0, 1.0),
+            Bottom => Vec2::new(0.5, 1.0),
+            BottomRight => Vec2::new(1.0, 1.0),
+        }
+    }
+}
+
+
+//////// Placement ////////
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Placement {
+    anchor: Anchor,
+    // (fractions of the virtual screen's size, e.g. 0.1 is 10%)
+    percent: Vec2,
+    // (virtual pixels)
+    offset: Vec2,
+}
+
+impl Placement {
+    pub const fn new(anchor: Anchor, offset: Vec2) -> Self {
+        Self { anchor, percent: Vec2::ZERO, offset }
+    }
+
+    // a fraction of the way across and down the virtual screen
+    pub const fn percent(x: f32, y: f32) -> Self {
+        Self { anchor: Anchor::TopLeft, percent: Vec2::new(x, y), offset: Vec2::ZERO }
+    }
+
+    // where it is on the virtual screen
+    pub fn loc(&self) -> Vec2 {
+        let size = Vec2::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT);
+        (self.anchor.fraction() + self.percent) * size + self.offset
+    }
+}
+
+
+//////// Layout ////////
+// how the virtual screen fits into the window
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Layout {
+    // window pixels per virtual pixel
+    scale: f32,
+    // where the virtual screen's top left corner is in the window
+    origin: Vec2,
+    // the window's drawable size (in window pixels)
+    window_size: Vec2,
+}
+
+impl Layout {
+    // (window_size is ctx.gfx.drawable_size())
+    pub fn new(window_size: (f32, f32)) -> Self {
+        // (a minimized window can be 0x0, which would make the scale 0)
+        let window_size = Vec2::from(window_size).max(Vec2::ONE);
+        let scale = (window_size.x / VIRTUAL_WIDTH).min(window_size.y / VIRTUAL_HEIGHT);
+        let origin = 0.5 * (window_size - scale * Vec2::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT));
+        Self { scale, origin, window_size }
+    }
+
+    // make everything drawn on canvas from now on be drawn in virtual pixels
+    pub fn apply(&self, canvas: &mut Canvas) {
+        canvas.set_screen_coordinates(self.screen_coordinates());
+    }
+
+    // the part of the virtual screen (and beyond) that the window shows
+    fn screen_coordinates(&self) -> Rect {
+        let top_left = -self.origin / self.scale;
+        let size = self.window_size / self.scale;
+        Rect::new(top_left.x, top_left.y, size.x, size.y)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_virtual_screen_is_letterboxed() {
+        // same shape, just scaled
+        let layout = Layout::new((VIRTUAL_WIDTH, VIRTUAL_HEIGHT));
+        assert_eq!((layout.scale, layout.origin), (1.0, Vec2::ZERO));
+        let layout = Layout::new((2.0 * VIRTUAL_WIDTH, 2.0 * VIRTUAL_HEIGHT));
+        assert_eq!((layout.scale, layout.origin), (2.0, Vec2::ZERO));
+        // too tall: bars above and below
+        let layout = Layout::new((960.0, 740.0));
+        assert_eq!((layout.scale, layout.origin), (0.5, Vec2::new(0.0, 100.0)));
+        assert_eq!(layout.screen_coordinates(), Rect::new(0.0, -200.0, VIRTUAL_WIDTH, 1480.0));
+        // too wide: bars left and right
+        let layout = Layout::new((VIRTUAL_WIDTH + 400.0, VIRTUAL_HEIGHT));
+        assert_eq!((layout.scale, layout.origin), (1.0, Vec2::new(200.0, 0.0)));
+        assert_eq!(layout.screen_coordinates(), Rect::new(-200.0, 0.0, VIRTUAL_WIDTH + 400.0, VIRTUAL_HEIGHT));
+    }
+
+    #[test]
+    fn minimized_windows_dont_break_the_layout() {
+        for window_size in [(0.0, 0.0), (0.0, 600.0), (800.0, 0.0)] {
+            let layout = Layout::new(window_size);
+            assert!(layout.scale > 0.0 && layout.scale.is_finite(), "{:?}", window_size);
+            assert!(layout.origin.is_finite(), "{:?}", window_size);
+            let rect = layout.screen_coordinates();
+            assert!([rect.x, rect.y, rect.w, rect.h].iter().all(|x| x.is_finite()), "{:?}", window_size);
+            assert!(rect.w > 0.0 && rect.h > 0.0, "{:?}", window_size);
+        }
+    }
+}
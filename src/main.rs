@@ -1,11 +1,27 @@
 use ggez::{
     event,
     graphics,
-    Context, GameResult, input::keyboard::KeyInput,
+    Context, GameResult, input::keyboard::{KeyCode, KeyInput},
 };
 use std::path;
 
+mod bots;
+mod card_flip;
+mod card_sprites;
+mod easing;
+mod layout;
+use layout::Layout;
+mod procedural_cards;
 mod progress;
+mod protocol;
+mod rate_limit;
+mod replay;
+mod session;
+use session::*;
+mod state_sync;
+mod theme;
+mod timeline;
+mod transport;
 mod my_card_game;
 use my_card_game::*;
 mod standard_deck;
@@ -25,46 +41,115 @@ const SCREEN_HEIGHT: f32 = 800.0;
 #[cfg(not(debug_assertions))]
 const SCREEN_HEIGHT: f32 = 1080.0;
 
+// controlpads that get throttled more than this many times in a few seconds
+// are kicked from the game (None to only throttle them)
+const KICK_AFTER_VIOLATIONS: Option<u32> = Some(100);
 
-struct MainState {
-    resources: GameResources,
-    card_game: MyCardGame,
-    client_handles: Vec<String>,
-}
+// strategy for bots spawned with the B key
+const DEFAULT_BOT_STRATEGY: &str = "random";
 
-impl MainState {
-    fn new(ctx: &mut Context) -> GameResult<MainState> {
-        let state = MainState {
-            resources: GameResources::new(ctx),
-            card_game: MyCardGame::new(),
-            client_handles: vec![],
-        };
-        Ok(state)
-    }
+// difficulty of AI players added with the A key
+const DEFAULT_AI_DIFFICULTY: Difficulty = Difficulty::Medium;
 
-    fn track_controlpad_clients(&mut self) {
-        if let Ok(true) = controlpads::clients_changed() {
-            if let Ok(handles) = controlpads::get_client_handles() {
-                self.client_handles = handles;
-            } else {
-                println!("Warning: Failed to get client handles");
-            }
+
+//////// Options ////////
+// command line options
+// - --bots <N>: spawn N bots at startup
+// - --bot-strategy <strategy>: random, greedy or scripted:<step>,<step>,...
+//   (steps are deal, left, right or pass)
+// - --ai <N>: seat N AI players at startup
+// - --ai-difficulty <difficulty>: easy, medium or hard
+// - --record <file>: record the game to a replay file
+// - --replay <file>: play back a replay file instead of listening to controlpads
+// - --headless: (with --replay) replay without opening a window and exit with
+//   an error if the replay doesn't match the recording
+// - --resume: pick up the game that was autosaved before the game last stopped
+// - --log-events: print everything that happens in the game
+// - --theme <name>: start with this card skin and table (see theme.rs; the T
+//   key switches to the next one)
+struct Options {
+    bots: u32,
+    bot_strategy: String,
+    ai_players: u32,
+    ai_difficulty: Difficulty,
+    record: Option<String>,
+    replay: Option<String>,
+    headless: bool,
+    resume: bool,
+    log_events: bool,
+    theme: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            bots: 0,
+            bot_strategy: DEFAULT_BOT_STRATEGY.to_string(),
+            ai_players: 0,
+            ai_difficulty: DEFAULT_AI_DIFFICULTY,
+            record: None,
+            replay: None,
+            headless: false,
+            resume: false,
+            log_events: false,
+            theme: None,
         }
     }
+}
 
-    fn receive_controlpad_messages(&mut self) -> Vec<(String, String)> {
-        self.track_controlpad_clients();
-        let mut messages: Vec<(String, String)> = Vec::new();
-        for handle in &self.client_handles {
-            if let Ok(msgs) = controlpads::get_messages(handle) {
-                for msg in &msgs {
-                    messages.push((handle.to_string(), msg.to_string()));
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Options::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bots" => {
+                    options.bots = args.next().and_then(|x| x.parse().ok())
+                        .expect("--bots needs a number");
+                }
+                "--bot-strategy" => {
+                    options.bot_strategy = args.next().expect("--bot-strategy needs a strategy");
+                }
+                "--ai" => {
+                    options.ai_players = args.next().and_then(|x| x.parse().ok())
+                        .expect("--ai needs a number");
+                }
+                "--ai-difficulty" => {
+                    options.ai_difficulty = args.next().and_then(|x| Difficulty::from_str(&x))
+                        .expect("--ai-difficulty needs easy, medium or hard");
                 }
-            } else {
-                println!("WARNING: Error while gatting controlad messages");
+                "--record" => {
+                    options.record = Some(args.next().expect("--record needs a file"));
+                }
+                "--replay" => {
+                    options.replay = Some(args.next().expect("--replay needs a file"));
+                }
+                "--headless" => options.headless = true,
+                "--resume" => options.resume = true,
+                "--log-events" => options.log_events = true,
+                "--theme" => {
+                    options.theme = Some(args.next().expect("--theme needs a theme name"));
+                }
+                _ => println!("WARNING: unknown argument: {}", arg),
             }
         }
-        messages
+        options
+    }
+}
+
+
+struct MainState {
+    resources: GameResources,
+    session: GameSession,
+}
+
+impl MainState {
+    fn new(ctx: &mut Context, options: &Options) -> GameResult<MainState> {
+        let session = GameSession::new(options).map_err(ggez::GameError::CustomError)?;
+        Ok(MainState {
+            resources: GameResources::new(ctx, options.theme.as_deref())?,
+            session,
+        })
     }
 }
 
@@ -77,12 +162,10 @@ impl event::EventHandler<ggez::GameError> for MainState {
     // called once per frame (synchronous with MainState::draw())
     // default 60 frames per second
     fn update(&mut self, _ctx: &mut Context) -> GameResult {
-        // handle received controlpad messages
-        for (client, msg) in self.receive_controlpad_messages() {
-            self.card_game.handle_controlpad_message(client, msg);
+        // (a finished replay stays on its last frame)
+        if !self.session.replay_done() {
+            self.session.update();
         }
-        // update game
-        self.card_game.update();
         Ok(())
     }
 
@@ -90,26 +173,67 @@ impl event::EventHandler<ggez::GameError> for MainState {
     // default 60 frames per second
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
         let mut canvas =
-            graphics::Canvas::from_frame(ctx, graphics::Color::from([0.4, 0.9, 0.6, 1.0]));
+            graphics::Canvas::from_frame(ctx, self.resources.theme().table_color());
         // make things pixely instead of blury
         canvas.set_sampler(graphics::Sampler::nearest_clamp());
+        // draw in virtual pixels scaled to fit the window (see layout.rs)
+        Layout::new(ctx.gfx.drawable_size()).apply(&mut canvas);
         // draw MyCardGame
-        self.card_game.draw(&mut canvas, ctx, &mut self.resources)?;
+        self.session.card_game.draw(&mut canvas, ctx, &mut self.resources)?;
         // finished drawing, show it all on the screen!
         canvas.finish(ctx)?;
         Ok(())
     }
 
 
-    fn key_down_event(&mut self, _ctx: &mut Context, input: KeyInput, _repeat: bool) -> GameResult {
-        if let Some(keycode) = input.keycode {
-            self.card_game.handle_key_press(keycode);
+    fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, _repeat: bool) -> GameResult {
+        // T switches to the next theme (see theme.rs), even during a replay
+        if input.keycode == Some(KeyCode::T) {
+            self.resources.next_theme(ctx);
+            return Ok(());
+        }
+        // a replay only plays back what was recorded
+        if self.session.is_replaying() {
+            return Ok(());
+        }
+        match input.keycode {
+            // B adds a bot player
+            Some(KeyCode::B) => self.session.spawn_bot(),
+            // A seats an AI player
+            Some(KeyCode::A) => self.session.add_ai_player(),
+            Some(_) => self.session.key_press(),
+            None => (),
         }
         Ok(())
     }    
 }
 
+// play back a replay as fast as possible without a window
+fn run_headless(options: &Options) -> Result<(), String> {
+    if options.replay.is_none() {
+        return Err("--headless needs --replay".to_string());
+    }
+    let mut session = GameSession::new(options)?;
+    while !session.replay_done() {
+        session.update();
+    }
+    println!("replayed {} ticks: {}", session.tick(), session.card_game.replay_summary());
+    match session.failed_checks() {
+        0 => Ok(()),
+        n => Err(format!("{} checks didn't match the recording", n)),
+    }
+}
+
 pub fn main() -> GameResult {
+    let options = Options::from_args();
+    transport::set_replaying(options.replay.is_some());
+    if options.headless {
+        if let Err(e) = run_headless(&options) {
+            println!("ERROR: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
     let resource_dir = path::PathBuf::from("./resources");
     let cb = ggez::ContextBuilder::new("drawing", "ggez")
         .add_resource_path(resource_dir)
@@ -118,6 +242,6 @@ pub fn main() -> GameResult {
                      .resizable(true)
         );
     let (mut ctx, events_loop) = cb.build()?;
-    let state = MainState::new(&mut ctx).unwrap();
+    let state = MainState::new(&mut ctx, &options)?;
     event::run(ctx, events_loop, state)
 }
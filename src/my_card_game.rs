@@ -1,132 +1,185 @@
 use crate::standard_deck::*;
 
 mod draw_my_card_game;
-
-use crate::progress::*;
-use ggez::input::keyboard::KeyCode;
+mod rules;
+use rules::*;
+mod zone;
+use zone::*;
+mod game_event;
+pub use game_event::GameEvent;
+use game_event::GameEvents;
+mod card_registry;
+use card_registry::*;
+mod ai_player;
+pub use ai_player::Difficulty;
+use ai_player::AiPlayer;
+mod snapshot;
+pub use snapshot::GameSnapshot;
+
+use crate::protocol::*;
+use crate::state_sync::*;
+use crate::transport;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::SeedableRng;
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::Receiver;
+
+// the encoding this game prefers to talk to controlpads with (controlpads that
+// only speak the original protocol will always get Encoding::Colon)
+const PREFERRED_ENCODING: Encoding = Encoding::Json;
+// most players (human or AI) that can sit at the table
+const TABLE_SEATS: usize = 8;
+// how many of a player's latest actions (with ids) we remember, so resends of
+// any of them are replied to again rather than performed again (a controlpad
+// can have more than one action waiting for a reply)
+const REMEMBERED_ACTIONS: usize = 32;
+
+
+// the outcome of a player's action; the error is the reason we send back to the
+// controlpad when rejecting it
+type ActionResult = Result<(), &'static str>;
 
 
 //////// Helpers ////////
-fn parse_card(card_str: &str) -> Option<(&str, &str, &str)> {
-    let mut parts = card_str.split(",");
-    let side = parts.next()?;
-    let suit = parts.next()?;
-    let rank = parts.next()?;
-    return Some((side, suit, rank));
+fn send_server_message(handle: &str, msg: &ServerMessage, encoding: Encoding) {
+    transport::send_message(handle, &msg.encode(encoding))
+        .unwrap_or_else(|e| println!("WARNING: Error sending controlpad message: {}", e));
 }
 
 //////// Deck ////////
-struct Deck {
-    cards: Vec<CardSpec>,
-}
-
-impl Deck {
-    // randomized 52 cards
-    fn new() -> Self {
-        let mut cards: Vec<CardSpec> = Vec::new();
-        for suit in CARD_SUITS.iter().map(|x| x.to_str()) {
-            for rank in CARD_RANKS.iter().map(|x| x.to_str()) {
-                let card_spec = CardSpec::from_strs(suit, rank);
-                cards.push(card_spec);
-            }
+// randomized 52 cards
+fn shuffled_deck(rng: &mut StdRng) -> Vec<CardSpec> {
+    let mut cards: Vec<CardSpec> = Vec::new();
+    for suit in CARD_SUITS.iter().map(|x| x.to_str()) {
+        for rank in CARD_RANKS.iter().map(|x| x.to_str()) {
+            let card_spec = CardSpec::from_strs(suit, rank);
+            cards.push(card_spec);
         }
-        let mut deck = Self {
-            cards
-        };
-        deck.shuffle();
-        deck
-    }
-
-    fn shuffle(&mut self) {
-        self.cards.shuffle(&mut thread_rng());
     }
+    cards.shuffle(rng);
+    cards
 }
 
 
-//////// SplayProgression ////////
-const SPLAY_RISE_TIME: f32 = 0.2;
-const SPLAY_FLIP_TIME: f32 = 0.4;
-const SPLAY_TRAVEL_TIME: f32 = 1.5;
-enum SplayProgression {
-    Rise(Progression),
-    Flip(Progression),
-    Travel(Progression),
-    //Lower(Progression),
+//////// Player ////////
+// who makes the decisions for a player
+enum PlayerKind {
+    // a person with a controlpad
+    Human { handle: String },
+    // a computer player (it has no controlpad so the controlpad fields of its
+    // Player go unused)
+    Ai(AiPlayer),
+}
+
+struct Player {
+    // stays the same even if a human takes over an AI's seat
+    id: u32,
+    kind: PlayerKind,
+    name: String,
+    // where they sit around the table (0 to TABLE_SEATS - 1)
+    seat: usize,
+    // the left card then the right card
+    hand: Zone,
+    // what we know about the controller code on this player's controlpad
+    client: ClientInfo,
+    // which of this player's states their controlpad has acknowledged
+    sync: StateSync,
+    // ids and results of the latest actions this player's controlpad sent
+    // with an id, oldest first (so that a resent action isn't performed twice)
+    recent_actions: VecDeque<(u32, ActionResult)>,
 }
 
-impl SplayProgression {
-    fn new() -> Self {
-        use SplayProgression::*;
-        Rise(Progression::new(SPLAY_RISE_TIME))
+impl Player {
+    fn handle(&self) -> Option<&str> {
+        match &self.kind {
+            PlayerKind::Human { handle } => Some(handle),
+            PlayerKind::Ai(_) => None,
+        }
     }
 
-    fn update(&mut self) {
-        use SplayProgression::*;
-        match self {
-            Rise(p) => {
-                p.update();
-                if p.is_done() {
-                   *self = Flip(Progression::new(SPLAY_FLIP_TIME)); 
-                }
-            }
-            Flip(p) => {
-                p.update();
-                if p.is_done() {
-                   *self = Travel(Progression::new(SPLAY_TRAVEL_TIME)); 
-                }
-            }
-            Travel(p) => {
-                p.update();
-            }
+    fn is_client(&self, client: &str) -> bool {
+        self.handle() == Some(client)
+    }
+
+    fn is_ai(&self) -> bool {
+        matches!(self.kind, PlayerKind::Ai(_))
+    }
+
+    // AI players don't have a controlpad so they aren't sent anything
+    fn send_message(&self, msg: &ServerMessage) {
+        if let Some(handle) = self.handle() {
+            send_server_message(handle, msg, self.client.encoding);
         }
     }
 
-    fn is_done(&self) -> bool {
-        match self {
-            SplayProgression::Travel(p) => p.is_done(),
-            _ => false,
+    fn view(&self) -> PlayerView {
+        PlayerView {
+            name: self.name.clone(),
+            left_card: self.card(true).map(|x| x.to_string()),
+            right_card: self.card(false).map(|x| x.to_string()),
         }
     }
-}
 
+    fn card(&self, is_left: bool) -> Option<CardSpec> {
+        self.hand.get(hand_index(is_left))
+    }
 
-//////// Player ////////
-struct Player {
-    handle: String,
-    name: String,
-    left_card: Option<CardSpec>,
-    right_card: Option<CardSpec>,
-}
+    fn card_count(&self) -> usize {
+        self.hand.len()
+    }
 
-impl Player {
-    fn state_string(&self) -> String {
-        let lcard_state = self.left_card.map_or("".to_string(), |x| x.to_string());
-        let rcard_state = self.right_card.map_or("".to_string(), |x| x.to_string());
-        format!("{}:{}:{}", &self.name, lcard_state, rcard_state)
+    fn send_state(&mut self) {
+        if self.is_ai() {
+            return;
+        }
+        let msg = self.sync.next_message(self.view(), self.client.capabilities.patch);
+        self.send_message(&msg);
     }
 
-    fn send_message(&self, msg: &str) {
-        controlpads::send_message(&self.handle, msg)
-            .unwrap_or_else(|e| println!("WARNING: Error sending controlpad message: {}", e));
+    // send the full state no matter what the controlpad acked before
+    fn resync_state(&mut self) {
+        self.sync.resync();
+        self.send_state();
+    }
 
+    // resend our last state if the controlpad hasn't acked it in a while
+    fn update_sync(&mut self) {
+        if let Some(msg) = self.sync.update() {
+            self.send_message(&msg);
+        }
     }
-    
-    fn send_state(&self) {
-        self.send_message(&format!("state:playing:{}", self.state_string()));
+
+    // the result of the action with this id if it was performed recently
+    fn recent_action(&self, id: u32) -> Option<ActionResult> {
+        self.recent_actions.iter().find(|(x, _)| *x == id).map(|(_, result)| *result)
     }
 
-    fn revoke_card(&mut self, is_left: bool) {
-        if is_left {
-            self.left_card = None;
-        } else {
-            self.right_card = None;
+    fn remember_action(&mut self, id: u32, result: ActionResult) {
+        if self.recent_actions.len() >= REMEMBERED_ACTIONS {
+            self.recent_actions.pop_front();
+        }
+        self.recent_actions.push_back((id, result));
+    }
 
+    fn reply_to_action(&self, id: u32, result: ActionResult) {
+        let reply = match result {
+            Ok(()) => ServerMessage::Accept { id },
+            Err(reason) => ServerMessage::Reject { id, reason },
         };
+        self.send_message(&reply);
     }
 }
 
+// where the left or right card is in a hand
+fn hand_index(is_left: bool) -> usize {
+    if is_left { 0 } else { 1 }
+}
+
+fn new_hand(player_id: u32) -> Zone {
+    Zone::new(ZoneId::Hand { player_id }, ZoneOrder::Ordered, Visibility::OwnerOnly)
+}
+
 
 // Notice that the members of MyCardGame have no data for graphics. This is
 // so that the state of the game is separate from the representation (graphics)
@@ -135,137 +188,394 @@ impl Player {
 // - The "state" of the game is handled in this file and the "representation"
 //   of the game is handled in draw_my_card_game.rs
 pub struct MyCardGame {
-    //// cards
+    //// cards (zones)
     // deck: cards in the facedown deck in the center of the screen
-    deck: Deck,
-    // splayed_cards: cards at the top of the screen
-    splayed_cards: Vec<CardSpec>,
-    // splaying_cards: the card traveling from the deck to the splayed_cards area
-    splaying_cards: Vec<(CardSpec, SplayProgression)>,
-    // center_card: card in the center of the screen next to the deck
-    center_card: CardSpec,
-    // giving_card: facedown card that goes off the bottom of the screen to go to the player
-    giving_card: Option<(String, Progression)>,
+    deck: Zone,
+    // splayed: cards at the top of the screen
+    splayed: Zone,
+    // center: the card in the center of the screen next to the deck (there's
+    // always exactly one)
+    center: Zone,
+    // discard: center cards that have been played over (under the center card)
+    discard: Zone,
+    // card_registry: which zone every card is in (see card_registry.rs)
+    card_registry: CardRegistry,
+    //// events (see game_event.rs)
+    // events: everyone subscribed to what happens in the game
+    events: GameEvents,
+    // animation_events: the card animations' subscription
+    animation_events: Receiver<GameEvent>,
+    // controlpad_events: the subscription that keeps controlpads up to date
+    controlpad_events: Receiver<GameEvent>,
+    //// card animations
+    // moving_cards: cards being animated from one zone to another (see zone.rs)
+    moving_cards: Vec<MovingCard>,
     ////
     //players:
     players: Vec<Player>,
+    next_player_id: u32,
+    //// controlpads
+    // encoding: the encoding we'd like to use with controlpads
+    encoding: Encoding,
+    // clients: what we know about controlpads that haven't joined yet (a
+    // joined player's ClientInfo moves into its Player)
+    clients: HashMap<String, ClientInfo>,
+    // last_table_view: the public table state we last broadcast to controlpads
+    last_table_view: Option<TableView>,
+    ////
+    // rng: everything random in the game comes from here so that a game can be
+    // replayed from its seed
+    rng: StdRng,
 }
 
-const GIVING_TRAVEL_TIME: f32 = 1.0;
-
 impl MyCardGame {
-    pub fn new() -> Self {
-        let mut deck = Deck::new();
-        let center_card = deck.cards.pop().unwrap();
-        Self {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let deck = Zone::new(ZoneId::Deck, ZoneOrder::Ordered, Visibility::FaceDown)
+            .with_cards(shuffled_deck(&mut rng));
+        let mut events = GameEvents::new();
+        let animation_events = events.subscribe();
+        let controlpad_events = events.subscribe();
+        let mut game = Self {
             deck,
-            splayed_cards: Vec::new(),
-            splaying_cards: Vec::new(),
-            center_card,
-            giving_card: None,
+            splayed: Zone::new(ZoneId::Splayed, ZoneOrder::Ordered, Visibility::FaceUp),
+            center: Zone::new(ZoneId::Center, ZoneOrder::Unordered, Visibility::FaceUp),
+            discard: Zone::new(ZoneId::Discard, ZoneOrder::Unordered, Visibility::FaceUp),
+            card_registry: CardRegistry::new(),
+            events,
+            animation_events,
+            controlpad_events,
+            moving_cards: Vec::new(),
             players: Vec::new(),
-        }
+            next_player_id: 0,
+            encoding: PREFERRED_ENCODING,
+            clients: HashMap::new(),
+            last_table_view: None,
+            rng,
+        };
+        game.move_card(ZoneId::Deck, None, ZoneId::Center, None);
+        game
+    }
+
+    fn center_card(&self) -> CardSpec {
+        self.center.top().unwrap()
     }
 
     pub fn update(&mut self) {
-        // update splaying card
-        let mut i = 0;
-        while i < self.splaying_cards.len() {
-            let (card_spec, splay_p) = &mut self.splaying_cards[i];
-            if splay_p.is_done() {
-                self.splayed_cards.push(*card_spec);
-                self.splaying_cards.remove(i);
-            } else {
-                splay_p.update();
-                i += 1;
-            };
+        // animate cards changing zones
+        self.update_moving_cards();
+        // send controlpads anything they need to hear about
+        self.sync_controlpads();
+        // let AI players think (and act)
+        self.update_ai_players();
+        // resend states that controlpads haven't acked
+        for player in &mut self.players {
+            player.update_sync();
         }
-        // update giving card
-        let mut give_finish: Option<String> = None; // get around borrowing rules
-        if let Some((player_handle, prog)) = &mut self.giving_card {
-            prog.update();
-            if prog.is_done() {
-                give_finish = Some(player_handle.clone());
-                self.giving_card = None;
-            }
+        // tell controlpads about anything that changed on the table this tick
+        self.broadcast_table_view();
+        // animate the moves made this tick (e.g. by AI players) right away
+        self.start_moving_cards();
+        // catch cards being duplicated or lost as soon as it happens
+        if cfg!(debug_assertions) {
+            self.assert_cards_conserved();
         }
-        if let Some(player_handle) = give_finish {
-            self.finish_give_card(&player_handle);
+    }
+
+    fn table_view(&self) -> TableView {
+        TableView {
+            center_card: self.center_card().to_string(),
+            deck_size: self.deck.len(),
+            splayed_cards: self.splayed.cards().iter().map(|x| x.to_string()).collect(),
+            players: self.players.iter()
+                .map(|p| TablePlayerView {
+                    name: p.name.clone(),
+                    card_count: p.card_count(),
+                })
+                .collect(),
         }
     }
 
-    fn deal(&mut self) {
-        if let Some(next_card) = self.deck.cards.pop() {
-            self.splaying_cards.push((next_card, SplayProgression::new()));
+    // a short description of the game (used to check that a replay plays out
+    // the same way as the game it was recorded from)
+    pub fn replay_summary(&self) -> String {
+        let mut summary = ServerMessage::Table(self.table_view()).encode(Encoding::Colon);
+        for player in &self.players {
+            let view = player.view();
+            summary += &format!("|{}:{}:{}", view.name,
+                                view.left_card.unwrap_or_default(),
+                                view.right_card.unwrap_or_default());
         }
+        summary
     }
 
-    // Assumes player_handle is a valid handle for a player in self.players
-    fn start_give_card(&mut self, player_handle: String, side: &str, suit: &str, rank: &str) {
-        if self.giving_card.is_some() {
+    // every controlpad (joined or not) that wants table messages
+    fn table_watchers(&self) -> Vec<(String, ClientInfo)> {
+        let players = self.players.iter()
+            .filter_map(|p| Some((p.handle()?.to_string(), p.client)));
+        let clients = self.clients.iter().map(|(h, c)| (h.clone(), *c));
+        players.chain(clients)
+            .filter(|(_, c)| c.capabilities.table && c.is_supported())
+            .collect()
+    }
+
+    // send the table view to every watcher, but only if it changed since the
+    // last time we sent it (this is called once per tick so watchers get at
+    // most one table message per tick)
+    fn broadcast_table_view(&mut self) {
+        let view = self.table_view();
+        if self.last_table_view.as_ref() == Some(&view) {
             return;
         }
-        let player = self.players.iter_mut().find(|x| x.handle == player_handle).unwrap();
-        if let Some(next_card) = self.deck.cards.pop() {
-            self.center_card = CardSpec::from_strs(suit, rank);
-            player.revoke_card(side == "L");
-            // we set the card here, but we won't tell the player about it (via
-            // send_state()) until the giving_card progresses across the screen
-            if side == "L" {
-                player.left_card = Some(next_card);
-            } else {
-                player.right_card = Some(next_card);
-            }
-            self.giving_card = Some((player.handle.clone(), Progression::new(GIVING_TRAVEL_TIME)));
+        let msg = ServerMessage::Table(view.clone());
+        for (handle, info) in self.table_watchers() {
+            send_server_message(&handle, &msg, info.encoding);
+        }
+        self.last_table_view = Some(view);
+    }
+
+    fn send_table_view(&self, client: &str, info: ClientInfo) {
+        if info.capabilities.table {
+            send_server_message(client, &ServerMessage::Table(self.table_view()), info.encoding);
         }
     }
 
-    fn finish_give_card(&mut self, player_handle: &str) {
-        if let Some(player) = self.players.iter().find(|x| x.handle == player_handle) {
-            player.send_state();
+    fn deal(&mut self) -> ActionResult {
+        let card = self.move_card(ZoneId::Deck, None, ZoneId::Splayed, None).ok_or("deck-empty")?;
+        self.emit(GameEvent::CardDealt { card });
+        Ok(())
+    }
+
+    // Assumes player_id is the id of a player in self.players
+    fn start_give_card(&mut self, player_id: u32, is_left: bool) -> ActionResult {
+        if self.is_giving() {
+            return Err("busy");
         }
+        let player = self.players.iter().find(|x| x.id == player_id).unwrap();
+        let card = player.card(is_left).ok_or("not-your-card")?;
+        let hand = ZoneId::Hand { player_id };
+        if self.card_registry.zone(card) != hand {
+            return Err("not-your-card");
+        }
+        if self.deck.is_empty() {
+            return Err("deck-empty");
+        }
+        // the card being played over goes to the discard pile
+        self.move_card(ZoneId::Center, None, ZoneId::Discard, None);
+        self.move_card(hand, Some(hand_index(is_left)), ZoneId::Center, None);
+        self.emit(GameEvent::CardPlayed { player_id, card });
+        // the player gets a new card in its place, but we won't tell them about
+        // it (via send_state()) until it gets to them (GameEvent::CardReceived)
+        self.move_card(ZoneId::Deck, None, hand, Some(hand_index(is_left)));
+        Ok(())
+    }
+
+    // the first seat nobody is sitting in
+    fn free_seat(&self) -> Option<usize> {
+        (0..TABLE_SEATS).find(|seat| !self.players.iter().any(|x| x.seat == *seat))
+    }
+
+    fn add_player(&mut self, kind: PlayerKind, name: String, seat: usize) -> &mut Player {
+        let id = self.next_player_id;
+        let player = Player {
+            id,
+            kind,
+            name,
+            seat,
+            hand: new_hand(id),
+            client: ClientInfo::unknown(),
+            sync: StateSync::new(),
+            recent_actions: VecDeque::new(),
+        };
+        self.next_player_id += 1;
+        self.players.push(player);
+        self.emit(GameEvent::PlayerJoined { player_id: id });
+        // deal them their left and right cards
+        for _ in 0..2 {
+            self.move_card(ZoneId::Deck, None, ZoneId::Hand { player_id: id }, None);
+        }
+        self.players.last_mut().unwrap()
+    }
+
+    // fill an empty seat with a computer player (returns false if there are
+    // no empty seats)
+    pub fn add_ai_player(&mut self, difficulty: Difficulty) -> bool {
+        let Some(seat) = self.free_seat() else {
+            return false;
+        };
+        let name = format!("CPU {}", self.next_player_id + 1);
+        let ai = AiPlayer::new(difficulty, &mut self.rng);
+        self.add_player(PlayerKind::Ai(ai), name, seat);
+        true
+    }
+
+    // a human joining gets an empty seat, or if the table's full, takes over
+    // an AI player's seat (and cards)
+    fn join(&mut self, client: String, name: String, info: ClientInfo) -> Result<&mut Player, &'static str> {
+        self.clients.remove(&client);
+        let kind = PlayerKind::Human { handle: client };
+        let name = clean_name(&name);
+        let player = if let Some(seat) = self.free_seat() {
+            self.add_player(kind, name, seat)
+        } else if let Some(i) = self.players.iter().position(|x| x.is_ai()) {
+            let player_id = self.players[i].id;
+            self.emit(GameEvent::PlayerJoined { player_id });
+            let player = &mut self.players[i];
+            player.kind = kind;
+            player.name = name;
+            player
+        } else {
+            return Err("table-full");
+        };
+        player.client = info;
+        Ok(player)
     }
     
     
-    pub fn handle_key_press(&mut self, _key: KeyCode) {
-        self.deal();
+    // (any key deals)
+    pub fn handle_key_press(&mut self) {
+        self.deal().ok();
 
         //self.start_give_card();
     }
 
+    // remove a controlpad from the game (their cards go back under the deck)
+    pub fn kick(&mut self, client: &str) {
+        self.clients.remove(client);
+        if let Some(i) = self.players.iter().position(|x| x.is_client(client)) {
+            let hand = ZoneId::Hand { player_id: self.players[i].id };
+            while self.move_card(hand, None, ZoneId::Deck, Some(0)).is_some() {}
+            let player = self.players.remove(i);
+            self.emit(GameEvent::PlayerLeft { player_id: player.id });
+            send_server_message(client, &ServerMessage::Kicked, player.client.encoding);
+        } else {
+            send_server_message(client, &ServerMessage::Kicked, Encoding::Colon);
+        }
+    }
+
+    fn client_info(&self, client: &str) -> ClientInfo {
+        if let Some(player) = self.players.iter().find(|x| x.is_client(client)) {
+            return player.client;
+        }
+        *self.clients.get(client).unwrap_or(&ClientInfo::unknown())
+    }
+
+    fn set_client_info(&mut self, client: &str, info: ClientInfo) {
+        if let Some(player) = self.players.iter_mut().find(|x| x.is_client(client)) {
+            player.client = info;
+        } else {
+            self.clients.insert(client.to_string(), info);
+        }
+    }
+
+    // Performs an action for a player and replies to their controlpad if it
+    // gave the action an id. Controlpads resend actions they haven't heard
+    // back about, so an action with the same id as a recent one is only
+    // replied to again, not performed again.
+    // Assumes player_id is the id of a player in self.players
+    fn perform_action<F>(&mut self, player_id: u32, id: Option<u32>, action: F)
+    where F: FnOnce(&mut Self) -> ActionResult {
+        let player = self.players.iter().find(|x| x.id == player_id).unwrap();
+        let earlier_result = id.and_then(|id| player.recent_action(id));
+        let result = earlier_result.unwrap_or_else(|| action(self));
+        let player = self.players.iter_mut().find(|x| x.id == player_id).unwrap();
+        if let Some(id) = id {
+            if earlier_result.is_none() {
+                player.remember_action(id, result);
+            }
+            player.reply_to_action(id, result);
+        }
+    }
+
     pub fn handle_controlpad_message(&mut self, client: String, message: String) {
-        let mut parts = message.split(":");
-        let msg_type = parts.next().unwrap(); // first on a split is always some
-        if let Some(player) = self.players.iter_mut().find(|x| x.handle == client) {
-            match msg_type {
-                "state-request" => {
+        let msg = if let Some(msg) = ClientMessage::parse(&message) {
+            msg
+        } else {
+            println!("WARNING: bad controlpad message: {}", &message);
+            return;
+        };
+        // hellos (and state-requests from controlpads that didn't say hello)
+        // tell us about the controller code running on the controlpad (and
+        // which encoding it'll understand being told to reload in)
+        match &msg {
+            ClientMessage::Hello { version, capabilities } => {
+                let caps = Capabilities::from_names(capabilities.iter().map(|x| x.as_str()));
+                let info = ClientInfo::from_hello(*version, caps, self.encoding);
+                self.set_client_info(&client, info);
+                let reply = ServerMessage::Hello { accepted: info.is_supported() };
+                send_server_message(&client, &reply, info.encoding);
+                return;
+            }
+            ClientMessage::StateRequest { version } if !self.client_info(&client).said_hello => {
+                self.set_client_info(&client, ClientInfo::from_version(*version, self.encoding));
+            }
+            _ => (),
+        }
+        let info = self.client_info(&client);
+        if !info.is_supported() {
+            // stale controller code (or code too old to say hello); keep
+            // telling it to reload rather than acting on messages it may have
+            // gotten wrong
+            send_server_message(&client, &ServerMessage::Hello { accepted: false }, info.encoding);
+            return;
+        }
+        // a controlpad asking for its state has probably just (re)connected and
+        // missed any table broadcasts
+        if let ClientMessage::StateRequest { .. } = msg {
+            self.send_table_view(&client, info);
+        }
+        if let Some(player) = self.players.iter_mut().find(|x| x.is_client(&client)) {
+            let player_id = player.id;
+            match msg {
+                ClientMessage::StateRequest { .. } => {
                     // a state request after the player is already joined
-                    player.send_state();
+                    player.resync_state();
+                }
+                ClientMessage::Ack { seq } => {
+                    if !player.sync.ack(seq) {
+                        println!("WARNING: {} acked a state we never sent ({})", &player.name, seq);
+                        player.resync_state();
+                    }
+                }
+                ClientMessage::Deal { id } => {
+                    self.perform_action(player_id, id, |game| {
+                        game.apply_action(player_id, Action::Deal)
+                    });
                 }
-                "deal" => {
-                    self.deal();
+                ClientMessage::Card { side, suit, rank, id } => {
+                    self.perform_action(player_id, id, |game| {
+                        let action = game.card_action(player_id, &side, &suit, &rank)?;
+                        game.apply_action(player_id, action)
+                    });
                 }
-                "card" => {
-                    let card_parse = parse_card(parts.next().unwrap_or(""));
-                    let (side, suit, rank) = if let Some((a, b, c)) = card_parse { (a,b,c) } else {return};
-                    let handle = player.handle.clone();
-                    self.start_give_card(handle, side, suit, rank);
+                ClientMessage::Join { id, .. } => {
+                    // fine if it's a resend of the join that joined them
+                    self.perform_action(player_id, id, |_| Err("already-joined"));
+                }
+                ClientMessage::Hello { .. } => {
+                    println!("WARNING: bad player message: {}", &message);
                 }
-                _ => println!("WARNING: bad player message: {}", &message),
             }
-        } else if msg_type == "state-request" {
+        } else if let ClientMessage::StateRequest { .. } = msg {
             // a state request before the player has joined
-            controlpads::send_message(&client, "state:joining")
-                .unwrap_or_else(|e| println!("WARNING: failed to send message: {}", e));
-        } else if msg_type == "join" {
-            let new_player = Player {
-                handle: client,
-                name: parts.next().unwrap_or("").to_string(),
-                left_card: self.deck.cards.pop(),
-                right_card: self.deck.cards.pop(),
-            };
-            new_player.send_state();
-            self.players.push(new_player);
+            send_server_message(&client, &ServerMessage::Joining, info.encoding);
+        } else if let ClientMessage::Join { name, id } = msg {
+            match self.join(client.clone(), name, info) {
+                Ok(player) => {
+                    if let Some(id) = id {
+                        player.remember_action(id, Ok(()));
+                        player.reply_to_action(id, Ok(()));
+                    }
+                    player.resync_state();
+                }
+                Err(reason) => {
+                    if let Some(id) = id {
+                        let reply = ServerMessage::Reject { id, reason };
+                        send_server_message(&client, &reply, info.encoding);
+                    }
+                    println!("WARNING: {} couldn't join: {}", &client, reason);
+                }
+            }
         } else {
             println!("WARNING: a controlpad tried to send something other than \
                       'join' when it hadn't joined yet");
@@ -276,4 +586,91 @@ impl MyCardGame {
 }
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::local;
 
+    // replies (accept:<id> or reject:<id>:<reason>) the game sent to handle
+    fn replies(handle: &str) -> Vec<String> {
+        local::receive(handle).into_iter()
+            .filter(|x| x.starts_with("accept:") || x.starts_with("reject:"))
+            .collect()
+    }
+
+    #[test]
+    fn resent_actions_are_only_performed_once() {
+        let handle = "test-resent-actions";
+        local::connect(handle);
+        let mut game = MyCardGame::new(1);
+        let send = |game: &mut MyCardGame, msg: &str| {
+            game.handle_controlpad_message(handle.to_string(), msg.to_string());
+        };
+        send(&mut game, "hello:3");
+        send(&mut game, "join:alice:4");
+        let left_card = game.players[0].card(true).unwrap();
+        // a deal and a card play both waiting for replies
+        send(&mut game, "deal:5");
+        send(&mut game, &format!("card:L,{}:6", left_card));
+        assert_eq!(replies(handle), ["accept:4", "accept:5", "accept:6"]);
+        // their replies got lost so both are resent, the older one first
+        let deck_size = game.deck.len();
+        send(&mut game, "deal:5");
+        send(&mut game, &format!("card:L,{}:6", left_card));
+        send(&mut game, "join:alice:4");
+        assert_eq!(game.deck.len(), deck_size);
+        assert_eq!(replies(handle), ["accept:5", "accept:6", "accept:4"]);
+        // a new action is still performed
+        send(&mut game, "deal:7");
+        assert_eq!(game.deck.len(), deck_size - 1);
+        assert_eq!(replies(handle), ["accept:7"]);
+        local::disconnect(handle);
+    }
+
+    #[test]
+    fn humans_only_replace_ai_players_when_the_table_is_full() {
+        let mut game = MyCardGame::new(1);
+        let join = |game: &mut MyCardGame, handle: &str| {
+            local::connect(handle);
+            game.handle_controlpad_message(handle.to_string(), "hello:3".to_string());
+            game.handle_controlpad_message(handle.to_string(), format!("join:{}:1", handle));
+        };
+        assert!(game.add_ai_player(Difficulty::Medium));
+        join(&mut game, "test-seats-1");
+        assert_eq!(game.players.len(), 2);
+        assert!(game.players[0].is_ai());
+        while game.add_ai_player(Difficulty::Medium) {}
+        assert_eq!(game.players.len(), TABLE_SEATS);
+        // the table's full so the first AI player makes room
+        let ai_id = game.players[0].id;
+        join(&mut game, "test-seats-2");
+        assert_eq!(game.players.len(), TABLE_SEATS);
+        assert!(game.players[0].is_client("test-seats-2") && game.players[0].id == ai_id);
+        assert_eq!(game.players.iter().filter(|x| x.is_ai()).count(), TABLE_SEATS - 2);
+        local::disconnect("test-seats-1");
+        local::disconnect("test-seats-2");
+    }
+
+    #[test]
+    fn kicked_players_cards_stop_flying_to_them() {
+        let handle = "test-kicked-player";
+        local::connect(handle);
+        let mut game = MyCardGame::new(2);
+        let events = game.subscribe();
+        game.handle_controlpad_message(handle.to_string(), "hello:3".to_string());
+        game.handle_controlpad_message(handle.to_string(), "join:bob".to_string());
+        let player_id = game.players[0].id;
+        // their cards are still on their way to them when they're kicked
+        game.update();
+        game.kick(handle);
+        // (debug builds check every card's place on every update)
+        for _ in 0..600 {
+            game.update();
+        }
+        assert!(game.moving_cards.is_empty());
+        let received = events.try_iter()
+            .any(|x| matches!(x, GameEvent::CardReceived { player_id: id, .. } if id == player_id));
+        assert!(!received);
+        local::disconnect(handle);
+    }
+}
@@ -0,0 +1,129 @@
+use super::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// Computer players that sit at the table like anyone else. They don't have a
+// controlpad; instead they look at the game directly and pick from the
+// actions the rules allow, waiting a bit first so they don't act inhumanly fast.
+
+
+//////// Difficulty ////////
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Difficulty {
+    // plays at random (and sometimes does nothing)
+    Easy,
+    // usually plays well
+    Medium,
+    // always plays well, and quickly
+    Hard,
+}
+
+impl Difficulty {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "easy" => Some(Difficulty::Easy),
+            "medium" => Some(Difficulty::Medium),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    // range of ticks to wait between actions
+    fn think_ticks(&self) -> (u32, u32) {
+        match self {
+            Difficulty::Easy => (150, 300),
+            Difficulty::Medium => (90, 180),
+            Difficulty::Hard => (45, 120),
+        }
+    }
+}
+
+
+//////// AiPlayer ////////
+pub struct AiPlayer {
+    difficulty: Difficulty,
+    ticks_until_action: u32,
+}
+
+impl AiPlayer {
+    pub fn new(difficulty: Difficulty, rng: &mut StdRng) -> Self {
+        let mut ai = Self {
+            difficulty,
+            ticks_until_action: 0,
+        };
+        ai.start_thinking(rng);
+        ai
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    fn start_thinking(&mut self, rng: &mut StdRng) {
+        let (min, max) = self.difficulty.think_ticks();
+        self.ticks_until_action = rng.gen_range(min..=max);
+    }
+
+    // returns true when it's time to act
+    fn update(&mut self, rng: &mut StdRng) -> bool {
+        if self.ticks_until_action > 0 {
+            self.ticks_until_action -= 1;
+            return false;
+        }
+        self.start_thinking(rng);
+        true
+    }
+
+    // pick one of the legal actions (None to do nothing this time)
+    fn choose(&self, legal: &[Action], cards: &[(bool, CardSpec)], center_card: CardSpec,
+              rng: &mut StdRng) -> Option<Action> {
+        let random = legal.choose(rng).copied();
+        match self.difficulty {
+            Difficulty::Easy if rng.gen_bool(0.3) => None,
+            Difficulty::Easy => random,
+            Difficulty::Medium if rng.gen_bool(0.3) => random,
+            Difficulty::Medium | Difficulty::Hard => best_action(legal, cards, center_card),
+        }
+    }
+}
+
+// play the lowest card that still beats the center card (saving high cards
+// for later), otherwise deal
+fn best_action(legal: &[Action], cards: &[(bool, CardSpec)], center_card: CardSpec) -> Option<Action> {
+    let center_value = center_card.rank().value();
+    let play = cards.iter()
+        .filter(|(_, card)| card.rank().value() > center_value)
+        .min_by_key(|(_, card)| card.rank().value())
+        .map(|(is_left, _)| Action::Play { is_left: *is_left })
+        .filter(|action| legal.contains(action));
+    play.or(legal.iter().copied().find(|x| *x == Action::Deal))
+}
+
+
+//////// MyCardGame ////////
+impl MyCardGame {
+    pub(super) fn update_ai_players(&mut self) {
+        for i in 0..self.players.len() {
+            let ready = match &mut self.players[i].kind {
+                PlayerKind::Ai(ai) => ai.update(&mut self.rng),
+                PlayerKind::Human { .. } => false,
+            };
+            if !ready {
+                continue;
+            }
+            let player = &self.players[i];
+            let cards: Vec<(bool, CardSpec)> = [true, false].into_iter()
+                .filter_map(|is_left| Some((is_left, player.card(is_left)?)))
+                .collect();
+            let legal = self.legal_actions(player.id);
+            let PlayerKind::Ai(ai) = &player.kind else { continue };
+            let player_id = player.id;
+            if let Some(action) = ai.choose(&legal, &cards, self.center_card(), &mut self.rng) {
+                if let Err(e) = self.apply_action(player_id, action) {
+                    println!("WARNING: AI player couldn't {:?}: {}", action, e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use super::*;
+
+// Cards are plain Copy values, so nothing about CardSpec stops the same card
+// from ending up in two places (or disappearing). The registry gives each
+// physical card an id and keeps track of the zone it's in and whether it's
+// still flying there. Every move goes through here (from
+// MyCardGame::move_card()) so a card can only leave the zone it's actually in,
+// and debug builds check after every update that the registry agrees with
+// where the cards really are.
+
+
+//////// CardPlace ////////
+// where the registry has a card
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CardPlace {
+    // (a moving card is already in the zone it's going to as far as the game
+    // is concerned)
+    pub zone: ZoneId,
+    // still being animated on its way there (it has a MovingCard, see zone.rs)
+    pub in_flight: bool,
+}
+
+impl CardPlace {
+    fn resting(zone: ZoneId) -> Self {
+        Self { zone, in_flight: false }
+    }
+}
+
+
+//////// CardRegistry ////////
+// index into CardRegistry::cards
+pub type CardId = usize;
+
+pub struct CardRegistry {
+    cards: Vec<(CardSpec, CardPlace)>,
+}
+
+impl CardRegistry {
+    // every card starts in the deck
+    pub fn new() -> Self {
+        let mut cards = Vec::new();
+        for suit in CARD_SUITS.iter() {
+            for rank in CARD_RANKS.iter() {
+                let card = CardSpec::from_strs(suit.to_str(), rank.to_str());
+                cards.push((card, CardPlace::resting(ZoneId::Deck)));
+            }
+        }
+        Self { cards }
+    }
+
+    pub fn id(&self, card: CardSpec) -> CardId {
+        self.cards.iter().position(|(x, _)| *x == card).unwrap()
+    }
+
+    pub fn zone(&self, card: CardSpec) -> ZoneId {
+        self.cards[self.id(card)].1.zone
+    }
+
+    // a card that isn't where the move says it's coming from means the game
+    // logic has a bug (debug builds panic, release builds log it and move the
+    // card anyway)
+    // - the card is in flight until land() is called (a card that's moved
+    //   again before it lands is still in flight, to its new zone)
+    pub fn move_card(&mut self, card: CardSpec, from: ZoneId, to: ZoneId) {
+        let id = self.id(card);
+        let zone = self.cards[id].1.zone;
+        if zone != from {
+            let problem = format!("card {} ({}) moved from {:?} but it's in {:?}", id, card, from, zone);
+            if cfg!(debug_assertions) {
+                panic!("{}", problem);
+            }
+            println!("WARNING: {}", problem);
+        }
+        self.cards[id].1 = CardPlace { zone: to, in_flight: true };
+    }
+
+    // the card's animation has finished
+    pub fn land(&mut self, card: CardSpec) {
+        let id = self.id(card);
+        self.cards[id].1.in_flight = false;
+    }
+
+    // for when the cards have been put somewhere without moves (e.g. loading a
+    // save)
+    pub fn set_zone(&mut self, card: CardSpec, zone: ZoneId) {
+        let id = self.id(card);
+        self.cards[id].1 = CardPlace::resting(zone);
+    }
+
+    // checks that `cards` (every card in the game along with where it is) has
+    // every card exactly once, each where the registry has it
+    pub fn check_conserved(&self, cards: &[(CardSpec, CardPlace)]) -> Result<(), String> {
+        let mut seen = vec![false; self.cards.len()];
+        for (card, zone) in cards {
+            let id = self.id(*card);
+            if seen[id] {
+                return Err(format!("card {} ({}) is in the game twice", id, card));
+            }
+            seen[id] = true;
+            if *zone != self.cards[id].1 {
+                return Err(format!("card {} ({}) is in {:?} but the registry has it in {:?}",
+                                   id, card, zone, self.cards[id].1));
+            }
+        }
+        match seen.iter().position(|x| !x) {
+            Some(id) => Err(format!("card {} ({}) has gone missing", id, self.cards[id].0)),
+            None => Ok(()),
+        }
+    }
+
+    // cards that aren't in `cards`
+    pub fn missing_cards(&self, cards: &[(CardSpec, CardPlace)]) -> Vec<CardSpec> {
+        self.cards.iter()
+            .map(|(card, _)| *card)
+            .filter(|card| !cards.iter().any(|(x, _)| x == card))
+            .collect()
+    }
+}
+
+
+//////// MyCardGame ////////
+impl MyCardGame {
+    // every card in the game along with where it actually is
+    fn card_locations(&self) -> Vec<(CardSpec, CardPlace)> {
+        self.zones().into_iter()
+            .flat_map(|zone| zone.cards().iter().map(|x| {
+                (*x, CardPlace { zone: zone.id(), in_flight: self.is_moving(*x) })
+            }))
+            .collect()
+    }
+
+    // every moving card is on its way to the zone the card is in, and no card
+    // is moving twice at once
+    fn check_moving_cards(&self) -> Result<(), String> {
+        for (i, moving_card) in self.moving_cards.iter().enumerate() {
+            let ZoneEvent::CardMoved { card, to, .. } = moving_card.event;
+            if self.moving_cards[..i].iter().any(|x| x.card() == card) {
+                return Err(format!("card {} is moving twice", card));
+            }
+            let zone = self.card_registry.zone(card);
+            if zone != to {
+                return Err(format!("card {} is moving to {:?} but it's in {:?}", card, to, zone));
+            }
+        }
+        Ok(())
+    }
+
+    // make the registry match where the cards are (cards that aren't
+    // anywhere go to the discard pile)
+    pub(super) fn reset_card_registry(&mut self) -> Result<(), String> {
+        let missing = self.card_registry.missing_cards(&self.card_locations());
+        let mut discard = self.discard.cards().to_vec();
+        discard.extend(missing);
+        self.discard.set_cards(discard);
+        let cards = self.card_locations();
+        for (card, place) in &cards {
+            self.card_registry.set_zone(*card, place.zone);
+        }
+        self.card_registry.check_conserved(&cards)
+    }
+
+    pub(super) fn assert_cards_conserved(&self) {
+        let checked = self.card_registry.check_conserved(&self.card_locations())
+            .and_then(|_| self.check_moving_cards());
+        if let Err(e) = checked {
+            panic!("{}", e);
+        }
+    }
+}
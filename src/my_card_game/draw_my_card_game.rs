@@ -1,132 +1,287 @@
 use super::*;
 use ggez::{
-    graphics::{Canvas, DrawParam, Image}, Context, GameResult,
+    graphics::{Canvas, Color, DrawMode, DrawParam, Mesh, Rect}, Context, GameResult,
 };
 use glam::Vec2;
-use std::f32::consts::PI;
 
+use crate::card_flip::*;
+use crate::card_sprites::*;
+use crate::easing::*;
+use crate::layout::*;
 use crate::resources::*;
+use crate::standard_deck::CardFace;
 
-// TODO: move to sahred location
-const CARD_IMG_WIDTH: f32 = 148.0;
-const CARD_IMG_HEIGHT: f32 = 148.0;
-
-// screen locations
+// screen locations (see layout.rs)
+const SPLAYED_ROW: Placement = Placement::new(Anchor::TopLeft, Vec2::new(20.0, 40.0));
+// the splayed row squeezes its cards closer together (down to
+// SPLAYED_MIN_CARD_DISTANCE apart) to fit in SPLAYED_ROW_WIDTH, then wraps onto
+// more rows (up to SPLAYED_MAX_ROWS, after which it squeezes some more)
+const SPLAYED_ROW_WIDTH: f32 = VIRTUAL_WIDTH - 40.0;
 const SPLAYED_CARD_DISTANCE: f32 = 30.0;
+const SPLAYED_MIN_CARD_DISTANCE: f32 = 12.0;
+const SPLAYED_ROW_DISTANCE: f32 = 60.0;
+const SPLAYED_MAX_ROWS: usize = 3;
+const CENTER_CARD: Placement = Placement::new(Anchor::Center,
+                                              Vec2::new(-CARD_IMG_WIDTH / 2.0, -CARD_IMG_HEIGHT / 2.0));
+// where cards go when the player they're going to (or coming from) has left
+const NOBODYS_HAND: Placement = Placement::percent(0.6, 1.0);
+// the "BOT" badge drawn before AI players' names
+const BOT_BADGE_WIDTH: f32 = 76.0;
+const BOT_BADGE_HEIGHT: f32 = 36.0;
+
+// seats: the top left corner of each seat's panel (around the edge of the table,
+// leaving the top for the splayed row)
+const SEATS: [Placement; TABLE_SEATS] = [
+    Placement::new(Anchor::Bottom, Vec2::new(-SEAT_WIDTH / 2.0, -SEAT_HEIGHT - 10.0)),
+    Placement::new(Anchor::Left, Vec2::new(20.0, -SEAT_HEIGHT / 2.0 + 130.0)),
+    Placement::new(Anchor::Right, Vec2::new(-SEAT_WIDTH - 20.0, -SEAT_HEIGHT / 2.0 + 130.0)),
+    Placement::new(Anchor::Bottom, Vec2::new(-SEAT_WIDTH / 2.0 - 420.0, -SEAT_HEIGHT - 10.0)),
+    Placement::new(Anchor::Bottom, Vec2::new(-SEAT_WIDTH / 2.0 + 420.0, -SEAT_HEIGHT - 10.0)),
+    Placement::new(Anchor::Left, Vec2::new(20.0, -SEAT_HEIGHT / 2.0 - 130.0)),
+    Placement::new(Anchor::Right, Vec2::new(-SEAT_WIDTH - 20.0, -SEAT_HEIGHT / 2.0 - 130.0)),
+    Placement::new(Anchor::BottomLeft, Vec2::new(20.0, -SEAT_HEIGHT - 10.0)),
+];
+const SEAT_WIDTH: f32 = 280.0;
+const SEAT_HEIGHT: f32 = 120.0;
+// (inside the panel)
+const SEAT_NAME_OFFSET: Vec2 = Vec2::new(12.0, 8.0);
+const SEAT_CARDS_OFFSET: Vec2 = Vec2::new(12.0, 60.0);
+const SEAT_CARD_DISTANCE: f32 = 30.0;
+const SEAT_STATUS_OFFSET: Vec2 = Vec2::new(110.0, 70.0);
+const SEAT_STATUS_SCALE: f32 = 0.7;
+
+// draw order (higher is on top); moving cards are on top of everything
+const SEAT_Z: i32 = -10;
+const DECK_Z: i32 = 0;
+const PILE_Z: i32 = 10;
+const SPLAYED_Z: i32 = 20;
+const MOVING_Z: i32 = 100;
+
+// the center card and the discard pile under it look like a messy pile, each
+// card a little out of line (set MESSY_PILE to false for a neat pile)
+const MESSY_PILE: bool = true;
+const PILE_JITTER_OFFSET: f32 = 4.0;
+const PILE_JITTER_ROTATION: f32 = 0.08;
+// how many of the discard pile's top cards peek out from under the center card
+const DISCARD_CARDS_SHOWN: usize = 3;
 
 
 
 //////// Deck ////////
-impl Deck {
-    fn deck_height(&self) -> usize {
-        return ((self.cards.len() + 5) / 6) as usize
-    }
+// the deck is drawn as a stack one card higher for every 6 cards in it
+fn deck_height(card_count: usize) -> usize {
+    card_count.div_ceil(6)
+}
 
-    fn facedown_card_offset(&self, n: usize) -> Vec2 {
-        Vec2::new(0.0, n as f32 * -2.0)
-    }
-    
-    pub fn draw(&self, canvas: &mut Canvas, location: Vec2, res: &GameResources) {
-        // draw card_none.png to represent an empty deck
-        if self.cards.len() == 0 {
-            canvas.draw(res.get_placeholder(), location);
-            return;
-        }
-        // draw cards up to a certain height depending how many cards are left
-        for i in 0..self.deck_height() {
-            canvas.draw(res.deck_res.get_back_image(), location + self.facedown_card_offset(i));
-        }
-    }
+fn facedown_card_offset(n: usize) -> Vec2 {
+    Vec2::new(0.0, n as f32 * -2.0)
+}
 
-    fn top_offset(&self) -> Vec2 {
-        self.facedown_card_offset(self.deck_height())
+fn add_deck(sprites: &mut SpriteLayer, card_count: usize, location: Vec2) {
+    // draw card_none.png to represent an empty deck
+    if card_count == 0 {
+        sprites.add(CardSprite::new(CardFace::Placeholder, location, DECK_Z));
+        return;
+    }
+    // draw cards up to a certain height depending how many cards are left
+    for i in 0..deck_height(card_count) {
+        let card_loc = location + facedown_card_offset(i);
+        sprites.add(CardSprite::new(CardFace::Back, card_loc, DECK_Z));
     }
 }
 
+
 //////// MyCardGame ////////
 impl MyCardGame {
+    // (canvas should already have layout applied)
     pub fn draw(&self, canvas: &mut Canvas, ctx: &mut Context, res: &mut GameResources) -> GameResult<()> {
-        let (screen_width, screen_height) = ctx.gfx.drawable_size();
-        // draw splayed cards
-        let splayed_cards_loc = Vec2::new( 0.0, 40.0 );
-        for (i, card_spec) in self.splayed_cards.iter().enumerate() {
-            let card_loc = splayed_cards_loc + Vec2::new(SPLAYED_CARD_DISTANCE * i as f32, 0.0);
-            canvas.draw(res.deck_res.get_card_image(card_spec), card_loc);
+        let mut sprites = SpriteLayer::new();
+        // seats (under the cards so cards fly over them)
+        for player in &self.players {
+            self.draw_seat(canvas, ctx, &mut sprites, player, res)?;
         }
         //
-        // draw center card
-        let center_card_loc = Vec2::new(
-            (screen_width - CARD_IMG_WIDTH) / 2.0,
-            (screen_height - CARD_IMG_HEIGHT) / 2.0,
-        );
-        canvas.draw(res.deck_res.get_card_image(&self.center_card), center_card_loc);
-        //
-        // draw deck
-        let deck_loc = center_card_loc + Vec2::new(CARD_IMG_WIDTH, 0.0);
-        self.deck.draw(canvas, deck_loc, res);
-        //
-        // draw splaying cards
-        for i in (0..self.splaying_cards.len()).rev() {
-            let card_start = deck_loc + self.deck.top_offset();
-            let card_end = splayed_cards_loc + Vec2::new(SPLAYED_CARD_DISTANCE*(self.splayed_cards.len() + i) as f32, 0.0);
-            let (card_spec, splay_p) = &self.splaying_cards[i];
-            let card_img = res.deck_res.get_card_image(&card_spec);
-            let back_img = res.deck_res.get_back_image();
-            draw_splaying_card(canvas, splay_p, card_start, card_end, card_img, back_img);
+        // cards that are moving are drawn by add_moving_card() instead
+        let still_cards = |zone: &Zone| -> Vec<(usize, CardSpec)> {
+            zone.cards().iter().copied().enumerate()
+                .filter(|(_, card)| !self.is_moving(*card))
+                .collect()
+        };
+        // splayed cards
+        for (i, card_spec) in still_cards(&self.splayed) {
+            let card_loc = self.zone_card_loc(ZoneId::Splayed, i);
+            sprites.add(CardSprite::new(CardFace::Front(card_spec), card_loc, SPLAYED_Z));
         }
         //
-        // draw giving card
-        if let Some((_, prog)) = &self.giving_card {
-            let start_loc = deck_loc + self.deck.top_offset();
-            let end_loc = Vec2::new(screen_width*0.6, screen_height);
-            let giving_loc = giving_card_loc(start_loc, end_loc, prog.progress());
-            canvas.draw(res.deck_res.get_back_image(), giving_loc);
+        // the top of the discard pile then the center card on top of it
+        let discard = still_cards(&self.discard);
+        let discard_shown = &discard[discard.len().saturating_sub(DISCARD_CARDS_SHOWN)..];
+        for (zone, cards) in [(ZoneId::Discard, discard_shown), (ZoneId::Center, &still_cards(&self.center))] {
+            for (i, card_spec) in cards {
+                let (jitter_offset, jitter_rotation) = pile_jitter(zone, *card_spec);
+                let mut sprite = CardSprite::new(CardFace::Front(*card_spec),
+                                                 self.zone_card_loc(zone, *i) + jitter_offset, PILE_Z);
+                sprite.rotation = jitter_rotation;
+                sprites.add(sprite);
+            }
         }
         //
-        // draw player names
-        let mut name_loc = Vec2::new(20.0, 200.0);
-        for player in &self.players {
-            canvas.draw(res.get_text_graphic(&player.name), name_loc);
-            name_loc += Vec2::new(0.0, 40.0);
+        // deck
+        let deck_loc = self.zone_card_loc(ZoneId::Deck, 0);
+        add_deck(&mut sprites, still_cards(&self.deck).len(), deck_loc);
+        //
+        // cards moving between zones
+        for (i, moving_card) in self.moving_cards.iter().enumerate() {
+            self.add_moving_card(&mut sprites, moving_card, MOVING_Z + i as i32);
         }
+        sprites.draw(canvas, ctx, res.deck_res.atlas());
         Ok(())
     }
 
-    
-}
+    fn add_moving_card(&self, sprites: &mut SpriteLayer, moving_card: &MovingCard, z: i32) {
+        let ZoneEvent::CardMoved { card, from, from_index, from_visibility, to, to_index, to_visibility } = moving_card.event;
+        let side = |visibility: Visibility| {
+            if visibility.is_face_up() { CardFace::Front(card) } else { CardFace::Back }
+        };
+        let transform = moving_card.transform();
+        // (cards leaving or landing on a pile start or end up out of line like
+        // the rest of the pile)
+        let (start_jitter, start_rotation) = pile_jitter(from, card);
+        let (end_jitter, end_rotation) = pile_jitter(to, card);
+        let start_loc = self.zone_card_loc(from, from_index) + start_jitter;
+        let end_loc = self.zone_card_loc(to, to_index) + end_jitter;
+        let path_loc = match to {
+            ZoneId::Hand { .. } => giving_card_loc(start_loc, end_loc, transform.travel),
+            _ => interpolate(start_loc, end_loc, Interpolation::Natural, transform.travel),
+        };
+        let mut sprite = CardSprite::new(side(from_visibility), path_loc + transform.offset, z);
+        sprite.other_side = side(to_visibility);
+        sprite.scale = transform.scale;
+        sprite.rotation = lerp_rotation(start_rotation, end_rotation, transform.travel) + transform.rotation;
+        sprite.flip = CardFlip::new(transform.flip, moving_card.flip_axis());
+        sprite.opacity = transform.opacity;
+        sprites.add(sprite);
+    }
 
-fn draw_splaying_card(canvas: &mut Canvas, splay_progress: &SplayProgression,
-                          start_loc: Vec2, end_loc: Vec2,
-                          card_img: &Image, back_img: &Image) {
-    use SplayProgression::*;
-    match splay_progress {
-        Rise(p) => {
-            let rising_loc = start_loc - p.progress() * Vec2::new(0.0, 12.0);
-            canvas.draw(back_img, rising_loc);
+    // a player's panel: their name, how many cards they have and what
+    // they're up to
+    // (the seat's cards are added to sprites)
+    fn draw_seat(&self, canvas: &mut Canvas, ctx: &mut Context, sprites: &mut SpriteLayer, player: &Player,
+                 res: &mut GameResources) -> GameResult<()> {
+        let seat_loc = SEATS[player.seat].loc();
+        let panel_rect = Rect::new(seat_loc.x, seat_loc.y, SEAT_WIDTH, SEAT_HEIGHT);
+        let panel = Mesh::new_rounded_rectangle(ctx, DrawMode::fill(), panel_rect, 12.0,
+                                                Color::new(1.0, 1.0, 1.0, 0.35))?;
+        canvas.draw(&panel, DrawParam::default());
+        let name_loc = seat_loc + SEAT_NAME_OFFSET;
+        if player.is_ai() {
+            draw_bot_badge(canvas, ctx, name_loc, res)?;
+            let badge_offset = Vec2::new(BOT_BADGE_WIDTH + 10.0, 0.0);
+            canvas.draw(res.get_text_graphic(&player.name), name_loc + badge_offset);
+        } else {
+            canvas.draw(res.get_text_graphic(&player.name), name_loc);
+        }
+        // a small card back for every card that's reached them
+        let hand = ZoneId::Hand { player_id: player.id };
+        for i in 0..player.hand.len() {
+            if player.hand.get(i).is_some_and(|x| !self.is_moving(x)) {
+                let mut sprite = CardSprite::new(CardFace::Back, self.zone_card_loc(hand, i), SEAT_Z);
+                sprite.scale = Vec2::splat(HAND_CARD_SCALE);
+                sprites.add(sprite);
+            }
         }
-        Flip(p) => {
-            let risen_loc = start_loc - Vec2::new(0.0, 12.0);
-            let flip_scale = (p.progress() * PI).cos().abs();
-            let flip_offset = (1.0 - flip_scale)*0.5*CARD_IMG_WIDTH;
-            let flip_loc = risen_loc + Vec2::new(flip_offset, 0.0);
-            let card_side = if p.progress() < 0.5 {
-                back_img
-            } else {
-                card_img
-            };
-            canvas.draw(card_side, DrawParam::default()
-                        .dest(flip_loc)
-                        .scale(Vec2::new(flip_scale, 1.0)));
+        let status = self.seat_status(player, hand);
+        canvas.draw(res.get_text_graphic(status), DrawParam::default()
+                    .dest(seat_loc + SEAT_STATUS_OFFSET)
+                    .scale(Vec2::splat(SEAT_STATUS_SCALE)));
+        Ok(())
+    }
+
+    fn seat_status(&self, player: &Player, hand: ZoneId) -> &'static str {
+        let getting_card = self.moving_cards.iter()
+            .any(|x| matches!(x.event, ZoneEvent::CardMoved { to, .. } if to == hand));
+        if getting_card {
+            "getting a card"
+        } else if player.is_ai() {
+            "thinking"
+        } else if !player.sync.is_synced() {
+            // (their controlpad hasn't acked its latest state)
+            "syncing"
+        } else {
+            "ready"
         }
-        Travel(p) => {
-            let risen_loc = start_loc - Vec2::new(0.0, 12.0);
-            let travel_loc = interpolate(risen_loc, end_loc, Interpolation::Natural, p.progress());
-            canvas.draw(card_img, travel_loc);
+    }
+
+    // where the card at index in a zone is drawn
+    fn zone_card_loc(&self, zone: ZoneId, index: usize) -> Vec2 {
+        let center_card_loc = CENTER_CARD.loc();
+        match zone {
+            // (uses how many cards are in the row now, so cards on their way
+            // to the row land where they'll stay)
+            ZoneId::Splayed => {
+                SPLAYED_ROW.loc() + splayed_card_offset(index, self.splayed.len(), SPLAYED_ROW_WIDTH)
+            }
+            ZoneId::Center | ZoneId::Discard => center_card_loc,
+            // (the top of the stack a card at index would make)
+            ZoneId::Deck => {
+                center_card_loc + Vec2::new(CARD_IMG_WIDTH, 0.0) + facedown_card_offset((index + 6) / 6)
+            }
+            // cards in a hand are shrunk down to HAND_CARD_SCALE around their
+            // centers at the player's seat
+            ZoneId::Hand { player_id } => {
+                let card_size = Vec2::new(CARD_IMG_WIDTH, CARD_IMG_HEIGHT);
+                let icon_size = HAND_CARD_SCALE * card_size;
+                let icon_loc = match self.players.iter().find(|x| x.id == player_id) {
+                    Some(player) => hand_icon_loc(SEATS[player.seat].loc(), index),
+                    None => NOBODYS_HAND.loc(),
+                };
+                icon_loc + 0.5 * icon_size - 0.5 * card_size
+            }
         }
     }
 }
 
+// where the card at index is in a splayed row of card_count cards that has to
+// fit in row_width
+fn splayed_card_offset(index: usize, card_count: usize, row_width: f32) -> Vec2 {
+    // (the room for the cards' left edges)
+    let room = row_width - CARD_IMG_WIDTH;
+    let fits_in_row = (room / SPLAYED_MIN_CARD_DISTANCE) as usize + 1;
+    let rows = card_count.div_ceil(fits_in_row).clamp(1, SPLAYED_MAX_ROWS);
+    let per_row = card_count.div_ceil(rows).max(1);
+    let distance = if per_row > 1 {
+        SPLAYED_CARD_DISTANCE.min(room / (per_row - 1) as f32)
+    } else {
+        SPLAYED_CARD_DISTANCE
+    };
+    let (row, column) = (index / per_row, index % per_row);
+    Vec2::new(distance * column as f32, SPLAYED_ROW_DISTANCE * row as f32)
+}
 
+// the top left corner of the small card for the card at index in the hand of
+// the player at seat_loc
+fn hand_icon_loc(seat_loc: Vec2, index: usize) -> Vec2 {
+    seat_loc + SEAT_CARDS_OFFSET + Vec2::new(SEAT_CARD_DISTANCE * index as f32, 0.0)
+}
+
+// how out of line a card in a pile is (no jitter for other zones)
+fn pile_jitter(zone: ZoneId, card: CardSpec) -> (Vec2, f32) {
+    match zone {
+        ZoneId::Center | ZoneId::Discard if MESSY_PILE => {
+            jitter(card, PILE_JITTER_OFFSET, PILE_JITTER_ROTATION)
+        }
+        _ => (Vec2::ZERO, 0.0),
+    }
+}
+
+fn draw_bot_badge(canvas: &mut Canvas, ctx: &mut Context, loc: Vec2, res: &mut GameResources) -> GameResult<()> {
+    let badge_rect = Rect::new(loc.x, loc.y, BOT_BADGE_WIDTH, BOT_BADGE_HEIGHT);
+    let badge = Mesh::new_rounded_rectangle(ctx, DrawMode::fill(), badge_rect, 8.0,
+                                            Color::from_rgb(240, 210, 110))?;
+    canvas.draw(&badge, DrawParam::default());
+    canvas.draw(res.get_text_graphic("BOT"), loc + Vec2::new(8.0, 0.0));
+    Ok(())
+}
 
 fn giving_card_loc(start_loc: Vec2, end_loc: Vec2, p: f32) -> Vec2 {
     let lift_loc = start_loc + Vec2::new(3.0, -15.0);
@@ -138,43 +293,87 @@ fn giving_card_loc(start_loc: Vec2, end_loc: Vec2, p: f32) -> Vec2 {
                  p)
 }
 
-enum Interpolation {
-    Linear,
-    SlowDown,
-    SpeedUp,
-    RoundStart,
-    RoundEnd,
-    RoundFull,
-    Natural,
-}
 
-// interpolation between two points (use Interpolation::Linear for constant speed)
-fn interpolate(start_loc: Vec2, end_loc: Vec2, interp: Interpolation, progress: f32) -> Vec2 {
-    use Interpolation::*;
-    let p = match interp {
-        Linear => progress,
-        SlowDown => progress.sqrt(),
-        SpeedUp => progress.powf(2.0),
-        RoundStart => 1.0 - (progress * PI/2.0).cos(),
-        RoundEnd => (progress * PI/2.0).sin(),
-        RoundFull =>  (1.0 + ((1.0 - progress) * PI).cos())/2.0,
-        Natural => {
-            let roundfull = (1.0 + ((1.0 - progress) * PI).cos())/2.0;
-            let roundend = (progress * PI/2.0).sin();
-            (roundfull + roundend)/2.0
-        }
-    };
-    //
-    (1.0 - p) * start_loc  +  p * end_loc
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    // a row with room for 11 cards at SPLAYED_CARD_DISTANCE and 26 at
+    // SPLAYED_MIN_CARD_DISTANCE (the real row never gets full enough to
+    // squeeze)
+    const ROW_WIDTH: f32 = CARD_IMG_WIDTH + 10.0 * SPLAYED_CARD_DISTANCE;
+
+    fn row(card_count: usize, row_width: f32) -> Vec<Vec2> {
+        (0..card_count).map(|i| splayed_card_offset(i, card_count, row_width)).collect()
+    }
 
-// interpolate along a curved path between start_loc and end_loc that curves
-// towards intermediary (the final interpolation is linear, change the function
-// if you want to do something different)
-fn interpolate2(start_loc: Vec2, intermediary_loc: Vec2, end_loc: Vec2,
-                interp1: Interpolation, interp2: Interpolation, progress: f32) -> Vec2 {
-    let interp_a = interpolate(start_loc, intermediary_loc, interp1, progress);
-    let interp_b = interpolate(intermediary_loc, end_loc, interp2, progress);
-    return interpolate(interp_a, interp_b, Interpolation::Linear, progress);
+    fn row_count(offsets: &[Vec2]) -> usize {
+        offsets.iter().map(|x| (x.y / SPLAYED_ROW_DISTANCE) as usize).max().unwrap() + 1
+    }
+
+    #[test]
+    fn splayed_rows_fit_then_squeeze_then_wrap() {
+        // fits at the usual distance
+        let offsets = row(11, ROW_WIDTH);
+        assert_eq!(row_count(&offsets), 1);
+        assert_eq!(offsets[1].x, SPLAYED_CARD_DISTANCE);
+        assert_eq!(offsets[10].x + CARD_IMG_WIDTH, ROW_WIDTH);
+        // squeezes to fill the same width
+        let offsets = row(12, ROW_WIDTH);
+        assert_eq!(row_count(&offsets), 1);
+        assert!(offsets[1].x < SPLAYED_CARD_DISTANCE);
+        assert!((offsets[11].x + CARD_IMG_WIDTH - ROW_WIDTH).abs() < 0.01);
+        let offsets = row(26, ROW_WIDTH);
+        assert_eq!(row_count(&offsets), 1);
+        assert!((offsets[1].x - SPLAYED_MIN_CARD_DISTANCE).abs() < 0.01);
+        // wraps (splitting the cards evenly between the rows)
+        let offsets = row(27, ROW_WIDTH);
+        assert_eq!(row_count(&offsets), 2);
+        assert_eq!(offsets[13].y, 0.0);
+        assert_eq!(offsets[14], Vec2::new(0.0, SPLAYED_ROW_DISTANCE));
+        let offsets = row(3 * 26, ROW_WIDTH);
+        assert_eq!(row_count(&offsets), SPLAYED_MAX_ROWS);
+        assert!((offsets[1].x - SPLAYED_MIN_CARD_DISTANCE).abs() < 0.01);
+        // then squeezes some more
+        let offsets = row(3 * 26 + 1, ROW_WIDTH);
+        assert_eq!(row_count(&offsets), SPLAYED_MAX_ROWS);
+        assert!(offsets[1].x < SPLAYED_MIN_CARD_DISTANCE);
+    }
+
+    #[test]
+    fn splayed_rows_stay_in_their_width() {
+        for row_width in [ROW_WIDTH, SPLAYED_ROW_WIDTH] {
+            for card_count in 1..=200 {
+                for offset in row(card_count, row_width) {
+                    assert!(offset.x >= 0.0 && offset.x + CARD_IMG_WIDTH <= row_width + 0.01,
+                            "{} cards went past {}", card_count, row_width);
+                    assert!(offset.y <= SPLAYED_ROW_DISTANCE * (SPLAYED_MAX_ROWS - 1) as f32);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dealt_cards_land_where_they_were_headed() {
+        let mut game = MyCardGame::new(3);
+        let mut headed_to = HashMap::new();
+        let mut landed = 0;
+        // (dealing every tick, so lots of cards are in the air at once)
+        while game.deal().is_ok() || !game.moving_cards.is_empty() {
+            game.update();
+            for moving_card in &game.moving_cards {
+                if let ZoneEvent::CardMoved { card, to: ZoneId::Splayed, to_index, .. } = moving_card.event {
+                    headed_to.insert(card, game.zone_card_loc(ZoneId::Splayed, to_index));
+                }
+            }
+            for (i, card) in game.splayed.cards().iter().enumerate().filter(|(_, x)| !game.is_moving(**x)) {
+                if let Some(target) = headed_to.remove(card) {
+                    assert_eq!(game.zone_card_loc(ZoneId::Splayed, i), target);
+                    landed += 1;
+                }
+            }
+        }
+        assert!(headed_to.is_empty());
+        assert_eq!(landed, game.splayed.len());
+    }
 }
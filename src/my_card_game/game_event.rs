@@ -0,0 +1,97 @@
+use super::*;
+use std::fmt;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+// What happens in the game, as a stream of events. The game logic only emits
+// events; anything that wants to react to them (the card animations, syncing
+// controlpads, logging, sound if the game ever gets some) subscribes and gets
+// its own copy of every event to handle whenever it likes, so none of them
+// have to know about each other.
+// - there are no turns in this game (anyone can deal or play whenever they
+//   want) so there's no event for a turn changing
+
+
+//////// GameEvent ////////
+#[derive(Clone, Copy)]
+pub enum GameEvent {
+    // a card changed zones (see zone.rs)
+    CardMoved(ZoneEvent),
+    // a card was dealt from the deck into the splayed row
+    CardDealt { card: CardSpec },
+    // a player played one of their cards onto the center
+    CardPlayed { player_id: u32, card: CardSpec },
+    // a card given to a player has reached them
+    CardReceived { player_id: u32, card: CardSpec },
+    // a player sat down (or a human took over an AI player's seat)
+    PlayerJoined { player_id: u32 },
+    PlayerLeft { player_id: u32 },
+    // the deck has run out so nothing more can be dealt or played
+    RoundEnded,
+}
+
+impl fmt::Display for GameEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use GameEvent::*;
+        match self {
+            CardMoved(ZoneEvent::CardMoved { card, from, to, .. }) => {
+                write!(f, "{} moved from {:?} to {:?}", card, from, to)
+            }
+            CardDealt { card } => write!(f, "{} dealt", card),
+            CardPlayed { player_id, card } => write!(f, "player {} played {}", player_id, card),
+            CardReceived { player_id, card } => write!(f, "player {} received {}", player_id, card),
+            PlayerJoined { player_id } => write!(f, "player {} joined", player_id),
+            PlayerLeft { player_id } => write!(f, "player {} left", player_id),
+            RoundEnded => write!(f, "round ended"),
+        }
+    }
+}
+
+
+//////// GameEvents ////////
+// hands out every event to every subscriber
+pub struct GameEvents {
+    subscribers: Vec<Sender<GameEvent>>,
+}
+
+impl GameEvents {
+    pub fn new() -> Self {
+        Self { subscribers: Vec::new() }
+    }
+
+    // events emitted from now on will show up in the receiver (dropping the
+    // receiver unsubscribes)
+    pub fn subscribe(&mut self) -> Receiver<GameEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn emit(&mut self, event: GameEvent) {
+        self.subscribers.retain(|x| x.send(event).is_ok());
+    }
+}
+
+
+//////// MyCardGame ////////
+impl MyCardGame {
+    pub fn subscribe(&mut self) -> Receiver<GameEvent> {
+        self.events.subscribe()
+    }
+
+    pub(super) fn emit(&mut self, event: GameEvent) {
+        self.events.emit(event);
+    }
+
+    // the game's own subscription: controlpads hear about a new card once it
+    // reaches them
+    pub(super) fn sync_controlpads(&mut self) {
+        let events: Vec<GameEvent> = self.controlpad_events.try_iter().collect();
+        for event in events {
+            if let GameEvent::CardReceived { player_id, .. } = event {
+                if let Some(player) = self.players.iter_mut().find(|x| x.id == player_id) {
+                    player.send_state();
+                }
+            }
+        }
+    }
+}
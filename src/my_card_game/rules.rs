@@ -0,0 +1,64 @@
+use super::*;
+
+// The rules of the game as actions a player can take. Controlpad messages and
+// AI players both go through here so they play by the same rules.
+
+
+//////// Action ////////
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    // deal a card from the deck into the splayed row
+    Deal,
+    // play the left or right card onto the center (the player gets a new
+    // card from the deck in its place)
+    Play { is_left: bool },
+}
+
+
+//////// MyCardGame ////////
+impl MyCardGame {
+    // everything player_id could do right now
+    pub(super) fn legal_actions(&self, player_id: u32) -> Vec<Action> {
+        let mut actions = Vec::new();
+        let Some(player) = self.players.iter().find(|x| x.id == player_id) else {
+            return actions;
+        };
+        if self.deck.is_empty() {
+            return actions;
+        }
+        actions.push(Action::Deal);
+        if !self.is_giving() {
+            for is_left in [true, false] {
+                if player.card(is_left).is_some() {
+                    actions.push(Action::Play { is_left });
+                }
+            }
+        }
+        actions
+    }
+
+    // Assumes player_id is the id of a player in self.players
+    pub(super) fn apply_action(&mut self, player_id: u32, action: Action) -> ActionResult {
+        match action {
+            Action::Deal => self.deal(),
+            Action::Play { is_left } => self.start_give_card(player_id, is_left),
+        }
+    }
+
+    // the action for a controlpad's card:<side>,<suit>,<rank> message
+    // - the controlpad might be showing an old state (or lying to us) so the
+    //   card has to be the one the player actually holds
+    pub(super) fn card_action(&self, player_id: u32, side: &str, suit: &str, rank: &str) -> Result<Action, &'static str> {
+        let is_left = match side {
+            "L" => true,
+            "R" => false,
+            _ => return Err("bad-card"),
+        };
+        let card = CardSpec::try_from_strs(suit, rank).ok_or("bad-card")?;
+        let player = self.players.iter().find(|x| x.id == player_id).ok_or("not-your-card")?;
+        if player.card(is_left) != Some(card) {
+            return Err("not-your-card");
+        }
+        Ok(Action::Play { is_left })
+    }
+}
@@ -0,0 +1,176 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+// Serializable copies of the game (used for save files, and handy for test
+// fixtures). Only what matters to the players is kept: animations in progress
+// are finished early and controlpads get full states again once they
+// reconnect. Cards are written the way the protocol writes them ("hearts,05").
+
+
+//////// PlayerSnapshot ////////
+#[derive(Serialize, Deserialize)]
+pub struct PlayerSnapshot {
+    id: u32,
+    name: String,
+    seat: usize,
+    // the controlpad that gets this player back when it reconnects (None for
+    // AI players)
+    handle: Option<String>,
+    ai_difficulty: Option<Difficulty>,
+    left_card: Option<CardSpec>,
+    right_card: Option<CardSpec>,
+}
+
+impl Player {
+    fn snapshot(&self) -> PlayerSnapshot {
+        PlayerSnapshot {
+            id: self.id,
+            name: self.name.clone(),
+            seat: self.seat,
+            handle: self.handle().map(|x| x.to_string()),
+            ai_difficulty: match &self.kind {
+                PlayerKind::Ai(ai) => Some(ai.difficulty()),
+                PlayerKind::Human { .. } => None,
+            },
+            left_card: self.card(true),
+            right_card: self.card(false),
+        }
+    }
+
+    fn from_snapshot(snapshot: &PlayerSnapshot, rng: &mut StdRng) -> Result<Self, String> {
+        let kind = match (&snapshot.handle, snapshot.ai_difficulty) {
+            (Some(handle), _) => PlayerKind::Human { handle: handle.clone() },
+            (None, Some(difficulty)) => PlayerKind::Ai(AiPlayer::new(difficulty, rng)),
+            (None, None) => return Err(format!("player {} has no handle", &snapshot.name)),
+        };
+        let cards = snapshot.left_card.into_iter().chain(snapshot.right_card).collect();
+        Ok(Self {
+            id: snapshot.id,
+            kind,
+            name: snapshot.name.clone(),
+            // (checked in MyCardGame::from_snapshot())
+            seat: snapshot.seat,
+            hand: new_hand(snapshot.id).with_cards(cards),
+            // (filled in when the controlpad says hello again)
+            client: ClientInfo::unknown(),
+            sync: StateSync::new(),
+            recent_actions: VecDeque::new(),
+        })
+    }
+}
+
+
+//////// GameSnapshot ////////
+#[derive(Serialize, Deserialize)]
+pub struct GameSnapshot {
+    deck: Vec<CardSpec>,
+    splayed_cards: Vec<CardSpec>,
+    center_card: CardSpec,
+    discard_pile: Vec<CardSpec>,
+    players: Vec<PlayerSnapshot>,
+    next_player_id: u32,
+}
+
+impl GameSnapshot {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // written to a temporary file first so a crash mid-save can't leave a
+    // broken save behind
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).unwrap();
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, contents).map_err(|e| format!("{}: {}", &tmp_path, e))?;
+        fs::rename(&tmp_path, path).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+
+//////// MyCardGame ////////
+impl MyCardGame {
+    pub fn snapshot(&self) -> GameSnapshot {
+        GameSnapshot {
+            deck: self.deck.cards().to_vec(),
+            splayed_cards: self.splayed.cards().to_vec(),
+            center_card: self.center_card(),
+            discard_pile: self.discard.cards().to_vec(),
+            players: self.players.iter().map(|x| x.snapshot()).collect(),
+            next_player_id: self.next_player_id,
+        }
+    }
+
+    pub fn from_snapshot(snapshot: &GameSnapshot, seed: u64) -> Result<Self, String> {
+        let mut game = Self::new(seed);
+        game.deck.set_cards(snapshot.deck.clone());
+        game.splayed.set_cards(snapshot.splayed_cards.clone());
+        game.center.set_cards(vec![snapshot.center_card]);
+        game.discard.set_cards(snapshot.discard_pile.clone());
+        // (nothing to animate, the cards are already where they belong)
+        while game.animation_events.try_recv().is_ok() {}
+        game.next_player_id = snapshot.next_player_id;
+        for player in &snapshot.players {
+            let player = Player::from_snapshot(player, &mut game.rng)?;
+            game.players.push(player);
+        }
+        // players off the table (or sitting in someone else's seat) get a free
+        // one (in case a save was edited by hand)
+        for i in 0..game.players.len() {
+            let seat = game.players[i].seat;
+            if seat >= TABLE_SEATS || game.players[..i].iter().any(|x| x.seat == seat) {
+                game.players[i].seat = game.free_seat()
+                    .ok_or_else(|| format!("no seat for player {}", &game.players[i].name))?;
+            }
+        }
+        game.reset_card_registry()?;
+        Ok(game)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::local;
+
+    #[test]
+    fn resumed_players_get_their_seats_back() {
+        let handle = "test-snapshot-alice";
+        local::connect(handle);
+        let mut game = MyCardGame::new(1);
+        game.handle_controlpad_message(handle.to_string(), "hello:3".to_string());
+        game.handle_controlpad_message(handle.to_string(), "join:alice:1".to_string());
+        game.add_ai_player(Difficulty::Hard);
+        for _ in 0..300 {
+            game.update();
+        }
+        let path = std::env::temp_dir().join(format!("snapshot-test-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        game.snapshot().save(path).unwrap();
+        let resumed = MyCardGame::from_snapshot(&GameSnapshot::load(path).unwrap(), 2).unwrap();
+        std::fs::remove_file(path).ok();
+        assert!(resumed.deck.cards() == game.deck.cards());
+        assert!(resumed.splayed.cards() == game.splayed.cards());
+        assert!(resumed.center_card() == game.center_card());
+        assert_eq!(resumed.players.len(), 2);
+        for (resumed, player) in resumed.players.iter().zip(&game.players) {
+            assert_eq!((resumed.id, &resumed.name, resumed.seat), (player.id, &player.name, player.seat));
+            assert_eq!(resumed.view(), player.view());
+            assert_eq!(resumed.is_ai(), player.is_ai());
+        }
+        // alice's controlpad reconnects and gets her back
+        let mut game = resumed;
+        local::receive(handle);
+        game.handle_controlpad_message(handle.to_string(), "hello:3".to_string());
+        game.handle_controlpad_message(handle.to_string(), "state-request:3".to_string());
+        let alice = game.players.iter().find(|x| x.is_client(handle)).unwrap();
+        let state = ServerMessage::Playing { view: alice.view(), seq: None }.encode(Encoding::Colon);
+        assert!(local::receive(handle).contains(&state));
+        let deck_size = game.deck.len();
+        game.handle_controlpad_message(handle.to_string(), "deal:2".to_string());
+        assert_eq!(game.deck.len(), deck_size - 1);
+        local::disconnect(handle);
+    }
+}
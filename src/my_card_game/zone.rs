@@ -0,0 +1,350 @@
+use super::*;
+use crate::easing::Interpolation;
+use crate::timeline::*;
+use glam::Vec2;
+use std::f32::consts::PI;
+
+// Every card in the game is in exactly one zone: the deck, the center, the
+// splayed row, the discard pile or a player's hand. Cards only ever change
+// zones through MyCardGame::move_card(), which emits a ZoneEvent for each
+// move; those events are turned into MovingCard animations automatically so
+// the game logic never has to animate anything itself. (The card registry
+// counts a card as in flight until its MovingCard lands.)
+
+
+//////// ZoneId ////////
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ZoneId {
+    Deck,
+    Center,
+    Splayed,
+    // played over (when a card is played onto the center, the old center card
+    // goes here)
+    Discard,
+    Hand { player_id: u32 },
+}
+
+// whether a zone's cards keep the order they're put in (e.g. the deck or a
+// hand's left and right card) or it's just a pile
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ZoneOrder {
+    Ordered,
+    Unordered,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Visibility {
+    FaceUp,
+    FaceDown,
+    // only the player the zone belongs to can see the cards (on their
+    // controlpad); face down to everyone else
+    OwnerOnly,
+}
+
+impl Visibility {
+    pub fn is_face_up(&self) -> bool {
+        *self == Visibility::FaceUp
+    }
+}
+
+
+//////// Zone ////////
+pub struct Zone {
+    id: ZoneId,
+    order: ZoneOrder,
+    visibility: Visibility,
+    // bottom to top (or left to right)
+    cards: Vec<CardSpec>,
+}
+
+impl Zone {
+    pub fn new(id: ZoneId, order: ZoneOrder, visibility: Visibility) -> Self {
+        Self {
+            id,
+            order,
+            visibility,
+            cards: Vec::new(),
+        }
+    }
+
+    pub fn with_cards(mut self, cards: Vec<CardSpec>) -> Self {
+        self.set_cards(cards);
+        self
+    }
+
+    // puts cards in the zone without moving them from anywhere (so it's up
+    // to the caller to keep the card registry right)
+    pub fn set_cards(&mut self, cards: Vec<CardSpec>) {
+        self.cards = cards;
+    }
+
+    pub fn id(&self) -> ZoneId {
+        self.id
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+
+    pub fn cards(&self) -> &[CardSpec] {
+        &self.cards
+    }
+
+    pub fn len(&self) -> usize {
+        self.cards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<CardSpec> {
+        self.cards.get(index).copied()
+    }
+
+    pub fn top(&self) -> Option<CardSpec> {
+        self.cards.last().copied()
+    }
+
+    // (None for the top card)
+    fn remove(&mut self, index: Option<usize>) -> Option<(CardSpec, usize)> {
+        let index = index.or(self.cards.len().checked_sub(1))?;
+        if index >= self.cards.len() {
+            return None;
+        }
+        Some((self.cards.remove(index), index))
+    }
+
+    // returns where the card ended up
+    // - ordered zones put the card at `index` (None for the top)
+    // - unordered zones always put it on top
+    fn insert(&mut self, card: CardSpec, index: Option<usize>) -> usize {
+        let index = match (self.order, index) {
+            (ZoneOrder::Ordered, Some(index)) => index.min(self.cards.len()),
+            _ => self.cards.len(),
+        };
+        self.cards.insert(index, card);
+        index
+    }
+}
+
+
+//////// ZoneEvent ////////
+#[derive(Clone, Copy)]
+pub enum ZoneEvent {
+    CardMoved {
+        card: CardSpec,
+        from: ZoneId,
+        from_index: usize,
+        from_visibility: Visibility,
+        to: ZoneId,
+        to_index: usize,
+        to_visibility: Visibility,
+    },
+}
+
+
+//////// MovingCard ////////
+// how long each part of a card's move takes (in seconds)
+const MOVE_RISE_TIME: f32 = 0.2;
+const MOVE_FLIP_TIME: f32 = 0.4;
+const MOVE_TRAVEL_TIME: f32 = 1.2;
+// cards that start moving on the same tick leave one after another, this far
+// apart
+const MOVE_STAGGER_TIME: f32 = 0.15;
+// how far a card rises off its zone before it moves
+const MOVE_RISE_HEIGHT: f32 = 12.0;
+// how far (in radians) a card going to a player tips up off the table on its
+// way to them
+const GIVE_TILT: f32 = 0.6;
+// cards in a player's hand are shown this small at their seat
+pub(super) const HAND_CARD_SCALE: f32 = 0.3;
+
+// what a MovingCard's timeline fires
+enum MoveSignal {
+    Arrived,
+}
+
+// a card moving between zones: it rises off its zone, turns over if it's
+// going from face down to face up (or back), then travels
+pub struct MovingCard {
+    pub event: ZoneEvent,
+    timeline: Timeline<MoveSignal>,
+}
+
+impl MovingCard {
+    // (delay is in seconds)
+    fn new(event: ZoneEvent, delay: f32) -> Self {
+        let ZoneEvent::CardMoved { from, from_visibility, to, to_visibility, .. } = event;
+        let flips = from_visibility.is_face_up() != to_visibility.is_face_up();
+        let scale = |zone| match zone {
+            ZoneId::Hand { .. } => HAND_CARD_SCALE,
+            _ => 1.0,
+        };
+        let gives = matches!(to, ZoneId::Hand { .. });
+        Self {
+            event,
+            timeline: move_timeline(delay, flips, gives, (scale(from), scale(to))),
+        }
+    }
+
+    pub fn card(&self) -> CardSpec {
+        let ZoneEvent::CardMoved { card, .. } = self.event;
+        card
+    }
+
+    // (travel goes from the card's place in the zone it came from to its
+    // place in the zone it's going to; the card shows the side it had in the
+    // zone it came from until it's turned over)
+    pub fn transform(&self) -> Transform {
+        self.timeline.transform()
+    }
+
+    // which way the card turns over: sideways, or top to bottom when it's
+    // tipping up on its way to a player
+    pub fn flip_axis(&self) -> Vec2 {
+        match self.event {
+            ZoneEvent::CardMoved { to: ZoneId::Hand { .. }, .. } => Vec2::X,
+            _ => Vec2::Y,
+        }
+    }
+
+    // returns true once the card has arrived
+    fn update(&mut self) -> bool {
+        self.timeline.update().into_iter().any(|x| matches!(x, MoveSignal::Arrived))
+    }
+}
+
+// (scale is how big the card is in the zone it's coming from and the one it's
+// going to)
+fn move_timeline(delay: f32, flips: bool, gives: bool, scale: (f32, f32)) -> Timeline<MoveSignal> {
+    let risen = Vec2::new(0.0, -MOVE_RISE_HEIGHT);
+    let (start_scale, end_scale) = (Vec2::splat(scale.0), Vec2::splat(scale.1));
+    let mut steps = vec![
+        // (holds the starting size until it travels)
+        Timeline::tween(Track::Scale(start_scale, start_scale), 0.0, Interpolation::Linear),
+        Timeline::delay(delay),
+        Timeline::tween(Track::Offset(Vec2::ZERO, risen), MOVE_RISE_TIME, Interpolation::Linear),
+    ];
+    if flips {
+        steps.push(Timeline::tween(Track::Flip(0.0, PI), MOVE_FLIP_TIME, Interpolation::RoundFull));
+    }
+    let mut travel = vec![
+        Timeline::tween(Track::Offset(risen, Vec2::ZERO), MOVE_TRAVEL_TIME, Interpolation::Linear),
+        Timeline::tween(Track::Travel(0.0, 1.0), MOVE_TRAVEL_TIME, Interpolation::Linear),
+        Timeline::tween(Track::Scale(start_scale, end_scale), MOVE_TRAVEL_TIME, Interpolation::Natural),
+    ];
+    if gives {
+        travel.push(Timeline::sequence(vec![
+            Timeline::tween(Track::Flip(0.0, GIVE_TILT), MOVE_TRAVEL_TIME / 2.0, Interpolation::RoundEnd),
+            Timeline::tween(Track::Flip(GIVE_TILT, 0.0), MOVE_TRAVEL_TIME / 2.0, Interpolation::RoundStart),
+        ]));
+    }
+    steps.push(Timeline::parallel(travel));
+    Timeline::sequence(steps).then_fire(MoveSignal::Arrived)
+}
+
+
+//////// MyCardGame ////////
+impl MyCardGame {
+    pub(super) fn zone(&self, id: ZoneId) -> Option<&Zone> {
+        match id {
+            ZoneId::Deck => Some(&self.deck),
+            ZoneId::Center => Some(&self.center),
+            ZoneId::Splayed => Some(&self.splayed),
+            ZoneId::Discard => Some(&self.discard),
+            ZoneId::Hand { player_id } => {
+                self.players.iter().find(|x| x.id == player_id).map(|x| &x.hand)
+            }
+        }
+    }
+
+    fn zone_mut(&mut self, id: ZoneId) -> Option<&mut Zone> {
+        match id {
+            ZoneId::Deck => Some(&mut self.deck),
+            ZoneId::Center => Some(&mut self.center),
+            ZoneId::Splayed => Some(&mut self.splayed),
+            ZoneId::Discard => Some(&mut self.discard),
+            ZoneId::Hand { player_id } => {
+                self.players.iter_mut().find(|x| x.id == player_id).map(|x| &mut x.hand)
+            }
+        }
+    }
+
+    // the table's zones and every hand
+    pub(super) fn zones(&self) -> Vec<&Zone> {
+        let table = [&self.deck, &self.center, &self.splayed, &self.discard];
+        table.into_iter().chain(self.players.iter().map(|x| &x.hand)).collect()
+    }
+
+    // move the card at from_index in one zone to to_index in another (None for
+    // the top card / on top); returns the card that was moved, or None if there
+    // was no such card (or zone)
+    pub(super) fn move_card(&mut self, from: ZoneId, from_index: Option<usize>,
+                            to: ZoneId, to_index: Option<usize>) -> Option<CardSpec> {
+        let to_visibility = self.zone(to)?.visibility();
+        let from_zone = self.zone_mut(from)?;
+        let from_visibility = from_zone.visibility();
+        let (card, from_index) = from_zone.remove(from_index)?;
+        let to_index = self.zone_mut(to).unwrap().insert(card, to_index);
+        self.card_registry.move_card(card, from, to);
+        self.emit(GameEvent::CardMoved(ZoneEvent::CardMoved {
+            card, from, from_index, from_visibility, to, to_index, to_visibility,
+        }));
+        if from == ZoneId::Deck && self.deck.is_empty() {
+            self.emit(GameEvent::RoundEnded);
+        }
+        Some(card)
+    }
+
+    pub(super) fn is_moving(&self, card: CardSpec) -> bool {
+        self.moving_cards.iter().any(|x| x.card() == card)
+    }
+
+    // whether a card is on its way to someone's hand
+    pub(super) fn is_giving(&self) -> bool {
+        self.moving_cards.iter()
+            .any(|x| matches!(x.event, ZoneEvent::CardMoved { to: ZoneId::Hand { .. }, .. }))
+    }
+
+    // start animating the moves since the last update and move the
+    // animations along
+    pub(super) fn update_moving_cards(&mut self) {
+        self.start_moving_cards();
+        let mut finished = Vec::new();
+        self.moving_cards.retain_mut(|moving_card| {
+            let arrived = moving_card.update();
+            if arrived {
+                finished.push(moving_card.event);
+            }
+            !arrived
+        });
+        for event in finished {
+            self.finish_move(event);
+        }
+    }
+
+    // start animating the moves since this was last called
+    // - a card that moves again before it's landed (e.g. it was on its way to
+    //   a player who got kicked) stops where it was going and starts its new
+    //   move instead, so it's never animated twice or lands somewhere it no
+    //   longer is
+    pub(super) fn start_moving_cards(&mut self) {
+        let moves: Vec<ZoneEvent> = self.animation_events.try_iter()
+            .filter_map(|x| if let GameEvent::CardMoved(event) = x { Some(event) } else { None })
+            .collect();
+        for (i, event) in moves.into_iter().enumerate() {
+            let moving_card = MovingCard::new(event, i as f32 * MOVE_STAGGER_TIME);
+            self.moving_cards.retain(|x| x.card() != moving_card.card());
+            self.moving_cards.push(moving_card);
+        }
+    }
+
+    fn finish_move(&mut self, event: ZoneEvent) {
+        let ZoneEvent::CardMoved { card, to, .. } = event;
+        self.card_registry.land(card);
+        if let ZoneId::Hand { player_id } = to {
+            self.emit(GameEvent::CardReceived { player_id, card });
+        }
+    }
+}
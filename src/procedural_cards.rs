@@ -0,0 +1,105 @@
+use ggez::{
+    graphics::{Canvas, Color, DrawMode, DrawParam, Image, Mesh, Rect, Text, TextFragment, TextLayout},
+    Context, GameResult,
+};
+use glam::Vec2;
+
+use crate::standard_deck::*;
+
+// Plain card images drawn with meshes and text, for when a theme's card art
+// can't be loaded (so the game is still playable with no card art at all):
+// - fronts are white with the rank and suit in the corner and a big suit in
+//   the middle
+// - backs are a solid color with a border
+// - the placeholder (an empty deck) is just an outline
+
+
+// (sizes are for a CARD_IMG_WIDTH x CARD_IMG_HEIGHT image, everything's
+// scaled up or down for other sizes)
+// the card inside the image
+const CARD_SIZE: Vec2 = Vec2::new(104.0, 144.0);
+const CORNER_RADIUS: f32 = 10.0;
+const BORDER_WIDTH: f32 = 3.0;
+const BORDER_COLOR: Color = Color::new(0.15, 0.15, 0.15, 1.0);
+const RED_SUIT_COLOR: Color = Color::new(0.8, 0.1, 0.1, 1.0);
+const BLACK_SUIT_COLOR: Color = Color::new(0.1, 0.1, 0.1, 1.0);
+const BACK_COLOR: Color = Color::new(0.15, 0.3, 0.6, 1.0);
+const BACK_INSET: f32 = 10.0;
+const PLACEHOLDER_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.3);
+// (text sizes in pixels)
+const CORNER_TEXT_SIZE: f32 = 24.0;
+const CENTER_TEXT_SIZE: f32 = 64.0;
+
+
+// an image size big (the same size as the theme's card art) for face
+pub fn procedural_card(ctx: &mut Context, face: CardFace, size: Vec2) -> GameResult<Image> {
+    let size = size.round().max(Vec2::ONE);
+    let image = Image::new_canvas_image(ctx, ctx.gfx.surface_format(), size.x as u32, size.y as u32, 1);
+    let mut canvas = Canvas::from_image(ctx, image.clone(), Color::new(0.0, 0.0, 0.0, 0.0));
+    // (how much bigger everything is than at CARD_IMG_WIDTH x CARD_IMG_HEIGHT)
+    let k = (size / Vec2::new(CARD_IMG_WIDTH, CARD_IMG_HEIGHT)).min_element();
+    let card_size = k * CARD_SIZE;
+    let card_loc = 0.5 * (size - card_size);
+    let card_rect = Rect::new(card_loc.x, card_loc.y, card_size.x, card_size.y);
+    match face {
+        CardFace::Front(card) => {
+            draw_card_shape(&mut canvas, ctx, card_rect, Color::WHITE, k)?;
+            let color = suit_color(card.suit());
+            let corner_text = format!("{}{}", rank_label(card.rank()), suit_symbol(card.suit()));
+            let corner_loc = card_loc + Vec2::splat(k * (BORDER_WIDTH + 4.0));
+            canvas.draw(&Text::new(TextFragment::new(corner_text).color(color).scale(k * CORNER_TEXT_SIZE)),
+                        corner_loc);
+            let mut center_text = Text::new(TextFragment::new(suit_symbol(card.suit()))
+                                            .color(color).scale(k * CENTER_TEXT_SIZE));
+            center_text.set_layout(TextLayout::center());
+            canvas.draw(&center_text, card_loc + 0.5 * card_size);
+        }
+        CardFace::Back => {
+            draw_card_shape(&mut canvas, ctx, card_rect, BACK_COLOR, k)?;
+            let inset = k * BACK_INSET;
+            let inset = Rect::new(card_rect.x + inset, card_rect.y + inset,
+                                  card_rect.w - 2.0*inset, card_rect.h - 2.0*inset);
+            let inner_border = Mesh::new_rounded_rectangle(ctx, DrawMode::stroke(k * 2.0), inset,
+                                                           k * CORNER_RADIUS / 2.0, Color::WHITE)?;
+            canvas.draw(&inner_border, DrawParam::default());
+        }
+        CardFace::Placeholder => {
+            let outline = Mesh::new_rounded_rectangle(ctx, DrawMode::stroke(k * BORDER_WIDTH), card_rect,
+                                                      k * CORNER_RADIUS, PLACEHOLDER_COLOR)?;
+            canvas.draw(&outline, DrawParam::default());
+        }
+    }
+    canvas.finish(ctx)?;
+    Ok(image)
+}
+
+// a filled card with a border (k times as thick and round as usual)
+fn draw_card_shape(canvas: &mut Canvas, ctx: &mut Context, rect: Rect, color: Color, k: f32) -> GameResult<()> {
+    let fill = Mesh::new_rounded_rectangle(ctx, DrawMode::fill(), rect, k * CORNER_RADIUS, color)?;
+    let border = Mesh::new_rounded_rectangle(ctx, DrawMode::stroke(k * BORDER_WIDTH), rect,
+                                             k * CORNER_RADIUS, BORDER_COLOR)?;
+    canvas.draw(&fill, DrawParam::default());
+    canvas.draw(&border, DrawParam::default());
+    Ok(())
+}
+
+// (ranks are written "02" in file names but "2" on cards)
+fn rank_label(rank: CardRank) -> &'static str {
+    rank.to_str().trim_start_matches('0')
+}
+
+fn suit_symbol(suit: CardSuit) -> &'static str {
+    match suit {
+        CardSuit::Heart => "♥",
+        CardSuit::Diamond => "♦",
+        CardSuit::Spade => "♠",
+        CardSuit::Club => "♣",
+    }
+}
+
+fn suit_color(suit: CardSuit) -> Color {
+    match suit {
+        CardSuit::Heart | CardSuit::Diamond => RED_SUIT_COLOR,
+        CardSuit::Spade | CardSuit::Club => BLACK_SUIT_COLOR,
+    }
+}
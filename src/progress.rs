@@ -1,21 +1,5 @@
-
-// represents some event resulting from an update (also called a tick)
-// - add to this enum if you want to have events bubble up from elements lower
-//   in your hierarchy to be handle by elements higher in the hierarchy
-/*pub enum UpdateEvent {
-    None,
-    Finished,
-}
-
-impl UpdateEvent {
-    pub fn is_finished(&self) -> bool {
-        if let UpdateEvent::Finished = self {
-            return true;
-        }
-        return false;
-    }
-}
- */
+// how close to finishing (in ticks) counts as finished
+const TICK_SLACK: f32 = 0.001;
 
 // represents the progress of some occurence (usually the movement of something
 // across the screen)
@@ -43,9 +27,17 @@ impl Progression {
         self.progress
     }
     
-    // progress by per_tick amount
-    pub fn update(&mut self) {
-        self.progress += self.per_tick;
+    // progress by `ticks` ticks (which can include part of a tick); returns
+    // how many of them are left over after finishing (0.0 if it hasn't)
+    pub fn advance(&mut self, ticks: f32) -> f32 {
+        let ticks_to_finish = ((1.0 - self.progress) / self.per_tick).max(0.0);
+        // (a little slack so float error doesn't cost a whole extra tick)
+        if ticks < ticks_to_finish - TICK_SLACK {
+            self.progress += ticks * self.per_tick;
+            return 0.0;
+        }
+        self.progress = 1.0;
+        (ticks - ticks_to_finish).max(0.0)
     }
 
     pub fn is_done(&self) -> bool {
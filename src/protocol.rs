@@ -0,0 +1,569 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Messages between the game and the controlpads can be encoded two ways:
+// - Colon: the original `type:arg:arg` format described in protocol.md
+// - Json: `{"type": "...", ...}` objects, which can carry richer payloads
+// The game picks which encoding it prefers, but a controlpad only gets JSON if
+// it told us (in its hello or state-request) that it understands it. That way
+// old controllers keep working no matter which encoding the game prefers.
+
+// protocol versions
+// - 1: colon-delimited messages only (a bare `state-request` implies this)
+// - 2: colon-delimited or JSON messages
+// - 3: controlpads introduce themselves with a hello message
+pub const PROTOCOL_VERSION: u32 = 3;
+const FIRST_JSON_VERSION: u32 = 2;
+// controlpads that say hello with an older version than this, or don't say
+// hello at all, are told to reload (they are running a stale, cached copy of
+// the controller code)
+const MIN_HELLO_VERSION: u32 = 3;
+
+
+//////// Encoding ////////
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Encoding {
+    Colon,
+    Json,
+}
+
+impl Encoding {
+    // the encoding to use with a controlpad that speaks protocol `version`
+    // given the encoding that the game would prefer to use
+    pub fn negotiate(preferred: Encoding, version: u32) -> Encoding {
+        if version >= FIRST_JSON_VERSION {
+            preferred
+        } else {
+            Encoding::Colon
+        }
+    }
+}
+
+
+//////// Capabilities ////////
+// optional features a controlpad told us it supports in its hello message
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Capabilities {
+    pub json: bool,
+    // wants table messages
+    pub table: bool,
+    // understands patch messages (and acks the states it receives)
+    pub patch: bool,
+}
+
+impl Capabilities {
+    // unknown capabilities are ignored so newer controllers can talk to older
+    // games
+    pub fn from_names<'a>(names: impl Iterator<Item = &'a str>) -> Self {
+        let mut caps = Capabilities::default();
+        for name in names {
+            match name.trim() {
+                "json" => caps.json = true,
+                "table" => caps.table = true,
+                "patch" => caps.patch = true,
+                _ => (),
+            }
+        }
+        caps
+    }
+}
+
+
+//////// ClientInfo ////////
+// what we know about the controller code running on a particular controlpad
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ClientInfo {
+    pub version: u32,
+    pub capabilities: Capabilities,
+    pub encoding: Encoding,
+    // whether this came from a hello (as opposed to a bare state-request)
+    pub said_hello: bool,
+}
+
+impl ClientInfo {
+    // a controlpad we haven't heard a hello or state-request from yet
+    pub fn unknown() -> Self {
+        Self {
+            version: legacy_version(),
+            capabilities: Capabilities::default(),
+            encoding: Encoding::Colon,
+            said_hello: false,
+        }
+    }
+
+    // a controlpad that only told us its version in a state-request
+    pub fn from_version(version: u32, preferred: Encoding) -> Self {
+        Self {
+            version,
+            capabilities: Capabilities {
+                json: version >= FIRST_JSON_VERSION,
+                ..Capabilities::default()
+            },
+            encoding: Encoding::negotiate(preferred, version),
+            said_hello: false,
+        }
+    }
+
+    pub fn from_hello(version: u32, capabilities: Capabilities, preferred: Encoding) -> Self {
+        let encoding = if capabilities.json {
+            preferred
+        } else {
+            Encoding::Colon
+        };
+        Self {
+            version,
+            capabilities,
+            encoding,
+            said_hello: true,
+        }
+    }
+
+    // whether we can talk to this controlpad or it has to reload first
+    // (controller code that doesn't start with a hello is older than hellos)
+    pub fn is_supported(&self) -> bool {
+        self.said_hello && self.version >= MIN_HELLO_VERSION
+    }
+}
+
+
+//////// ClientMessage ////////
+// a message sent from a controlpad to the game
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ClientMessage {
+    Hello {
+        version: u32,
+        #[serde(default)]
+        capabilities: Vec<String>,
+    },
+    StateRequest {
+        #[serde(default = "legacy_version")]
+        version: u32,
+    },
+    // Join, Deal and Card are actions: if the controlpad gives them an id the
+    // game replies with an Accept or Reject carrying that id
+    Join {
+        #[serde(default)]
+        name: String,
+        #[serde(default)]
+        id: Option<u32>,
+    },
+    // acknowledges that the state (or patch) with this seq was applied
+    Ack {
+        seq: u32,
+    },
+    Deal {
+        #[serde(default)]
+        id: Option<u32>,
+    },
+    Card {
+        side: String,
+        suit: String,
+        rank: String,
+        #[serde(default)]
+        id: Option<u32>,
+    },
+}
+
+fn legacy_version() -> u32 {
+    1
+}
+
+fn parse_action_id(part: Option<&str>) -> Option<u32> {
+    part?.parse().ok()
+}
+
+// player names can't have these in them (they separate the fields of colon
+// messages and of the table encoding)
+pub const NAME_SEPARATORS: [char; 3] = [':', ',', ';'];
+
+pub fn clean_name(name: &str) -> String {
+    name.chars().filter(|x| !NAME_SEPARATORS.contains(x)).collect()
+}
+
+impl ClientMessage {
+    // parse a message in either encoding (JSON messages always start with '{')
+    pub fn parse(msg: &str) -> Option<ClientMessage> {
+        if msg.trim_start().starts_with('{') {
+            serde_json::from_str(msg).ok()
+        } else {
+            Self::parse_colon(msg)
+        }
+    }
+
+    fn parse_colon(msg: &str) -> Option<ClientMessage> {
+        let mut parts = msg.split(":");
+        let msg_type = parts.next()?; // first on a split is always some
+        match msg_type {
+            "hello" => {
+                let version = parts.next()?.parse().ok()?;
+                let capabilities = parts.next().unwrap_or("")
+                    .split(",")
+                    .filter(|x| !x.is_empty())
+                    .map(|x| x.to_string())
+                    .collect();
+                Some(ClientMessage::Hello { version, capabilities })
+            }
+            "state-request" => {
+                let version = parts.next()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(legacy_version());
+                Some(ClientMessage::StateRequest { version })
+            }
+            "join" => {
+                // (the id is always last, so a name with colons in it (from
+                // an older controller) doesn't lose its id)
+                let rest = msg.split_once(":").map_or("", |x| x.1);
+                let (name, id) = match rest.rsplit_once(":") {
+                    Some((name, id)) if id.parse::<u32>().is_ok() => (name, id.parse().ok()),
+                    _ => (rest, None),
+                };
+                Some(ClientMessage::Join { name: name.to_string(), id })
+            }
+            "ack" => Some(ClientMessage::Ack {
+                seq: parts.next()?.parse().ok()?,
+            }),
+            "deal" => Some(ClientMessage::Deal {
+                id: parse_action_id(parts.next()),
+            }),
+            "card" => {
+                let mut card_parts = parts.next()?.split(",");
+                let side = card_parts.next()?.to_string();
+                let suit = card_parts.next()?.to_string();
+                let rank = card_parts.next()?.to_string();
+                let id = parse_action_id(parts.next());
+                Some(ClientMessage::Card { side, suit, rank, id })
+            }
+            _ => None,
+        }
+    }
+}
+
+
+// just the type of a message in either encoding (without parsing the rest)
+pub fn message_type(msg: &str) -> String {
+    #[derive(Deserialize)]
+    struct TypeOnly {
+        #[serde(rename = "type")]
+        msg_type: String,
+    }
+    if msg.trim_start().starts_with('{') {
+        serde_json::from_str::<TypeOnly>(msg).map_or("".to_string(), |x| x.msg_type)
+    } else {
+        msg.split(":").next().unwrap_or("").to_string()
+    }
+}
+
+
+//////// ServerMessage ////////
+// the state of a joined player as seen by their own controlpad
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct PlayerView {
+    pub name: String,
+    pub left_card: Option<String>,
+    pub right_card: Option<String>,
+}
+
+// a changed field of a PlayerView (None means the field was emptied)
+pub type PlayerViewChange = (&'static str, Option<String>);
+
+impl PlayerView {
+    // the fields of self that are different from base
+    pub fn changes_from(&self, base: &PlayerView) -> Vec<PlayerViewChange> {
+        let mut changes = Vec::new();
+        if self.name != base.name {
+            changes.push(("name", Some(self.name.clone())));
+        }
+        if self.left_card != base.left_card {
+            changes.push(("left_card", self.left_card.clone()));
+        }
+        if self.right_card != base.right_card {
+            changes.push(("right_card", self.right_card.clone()));
+        }
+        changes
+    }
+}
+
+// the public state of the table that every controlpad can see
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct TableView {
+    pub center_card: String,
+    pub deck_size: usize,
+    pub splayed_cards: Vec<String>,
+    pub players: Vec<TablePlayerView>,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct TablePlayerView {
+    pub name: String,
+    pub card_count: usize,
+}
+
+// a message sent from the game to a controlpad
+pub enum ServerMessage {
+    // reply to a hello: either the controlpad can carry on or it must reload
+    Hello { accepted: bool },
+    Joining,
+    // full state of a player (seq is only given to controlpads that ack)
+    Playing {
+        view: PlayerView,
+        seq: Option<u32>,
+    },
+    // the changes that turn the state the controlpad acked as `base` into the
+    // state numbered `seq`
+    Patch {
+        seq: u32,
+        base: u32,
+        changes: Vec<PlayerViewChange>,
+    },
+    Table(TableView),
+    // replies to an action the controlpad gave an id
+    Accept {
+        id: u32,
+    },
+    Reject {
+        id: u32,
+        reason: &'static str,
+    },
+    // the controlpad has been removed from the game
+    Kicked,
+}
+
+// the shape of a ServerMessage when it's encoded as JSON
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum JsonServerMessage<'a> {
+    Hello {
+        result: &'static str,
+        version: u32,
+    },
+    State {
+        state: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        seq: Option<u32>,
+        #[serde(flatten)]
+        playing: Option<&'a PlayerView>,
+    },
+    Patch {
+        seq: u32,
+        base: u32,
+        changes: BTreeMap<&'static str, Option<&'a str>>,
+    },
+    Table(&'a TableView),
+    Accept {
+        id: u32,
+    },
+    Reject {
+        id: u32,
+        reason: &'static str,
+    },
+    Kicked,
+}
+
+impl ServerMessage {
+    pub fn encode(&self, encoding: Encoding) -> String {
+        match encoding {
+            Encoding::Colon => self.encode_colon(),
+            Encoding::Json => self.encode_json(),
+        }
+    }
+
+    fn encode_colon(&self) -> String {
+        match self {
+            ServerMessage::Hello { accepted } => {
+                format!("hello:{}:{}", hello_result(*accepted), PROTOCOL_VERSION)
+            }
+            ServerMessage::Joining => "state:joining".to_string(),
+            ServerMessage::Playing { view, seq } => {
+                let lcard = view.left_card.as_deref().unwrap_or("");
+                let rcard = view.right_card.as_deref().unwrap_or("");
+                let state = format!("state:playing:{}:{}:{}", view.name, lcard, rcard);
+                match seq {
+                    Some(seq) => format!("{}:{}", state, seq),
+                    None => state,
+                }
+            }
+            ServerMessage::Patch { seq, base, changes } => {
+                let changes: Vec<String> = changes.iter()
+                    .map(|(field, value)| format!("{}={}", field, value.as_deref().unwrap_or("")))
+                    .collect();
+                format!("patch:{}:{}:{}", seq, base, changes.join(";"))
+            }
+            ServerMessage::Table(view) => {
+                let players: Vec<String> = view.players.iter()
+                    .map(|p| format!("{},{}", p.name, p.card_count))
+                    .collect();
+                format!("table:{}:{}:{}:{}", view.center_card, view.deck_size,
+                        view.splayed_cards.join(";"), players.join(";"))
+            }
+            ServerMessage::Accept { id } => format!("accept:{}", id),
+            ServerMessage::Reject { id, reason } => format!("reject:{}:{}", id, reason),
+            ServerMessage::Kicked => "kicked".to_string(),
+        }
+    }
+
+    fn encode_json(&self) -> String {
+        let json_msg = match self {
+            ServerMessage::Hello { accepted } => JsonServerMessage::Hello {
+                result: hello_result(*accepted),
+                version: PROTOCOL_VERSION,
+            },
+            ServerMessage::Joining => JsonServerMessage::State {
+                state: "joining",
+                seq: None,
+                playing: None,
+            },
+            ServerMessage::Playing { view, seq } => JsonServerMessage::State {
+                state: "playing",
+                seq: *seq,
+                playing: Some(view),
+            },
+            ServerMessage::Patch { seq, base, changes } => JsonServerMessage::Patch {
+                seq: *seq,
+                base: *base,
+                changes: changes.iter()
+                    .map(|(field, value)| (*field, value.as_deref()))
+                    .collect(),
+            },
+            ServerMessage::Table(view) => JsonServerMessage::Table(view),
+            ServerMessage::Accept { id } => JsonServerMessage::Accept { id: *id },
+            ServerMessage::Reject { id, reason } => JsonServerMessage::Reject {
+                id: *id,
+                reason,
+            },
+            ServerMessage::Kicked => JsonServerMessage::Kicked,
+        };
+        // serializing plain structs of strings can't fail
+        serde_json::to_string(&json_msg).unwrap()
+    }
+}
+
+fn hello_result(accepted: bool) -> &'static str {
+    if accepted {
+        "ok"
+    } else {
+        "reload"
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playing_view() -> PlayerView {
+        PlayerView {
+            name: "alice".to_string(),
+            left_card: Some("hearts,05".to_string()),
+            right_card: None,
+        }
+    }
+
+    #[test]
+    fn colon_and_json_messages_parse_the_same() {
+        let pairs = [
+            ("hello:3:json,patch", r#"{"type":"hello","version":3,"capabilities":["json","patch"]}"#),
+            ("state-request:2", r#"{"type":"state-request","version":2}"#),
+            ("join:alice:7", r#"{"type":"join","name":"alice","id":7}"#),
+            ("ack:12", r#"{"type":"ack","seq":12}"#),
+            ("deal:8", r#"{"type":"deal","id":8}"#),
+            ("card:L,hearts,05:9", r#"{"type":"card","side":"L","suit":"hearts","rank":"05","id":9}"#),
+        ];
+        for (colon, json) in pairs {
+            let parsed = ClientMessage::parse(colon);
+            assert!(parsed.is_some(), "couldn't parse {}", colon);
+            assert_eq!(parsed, ClientMessage::parse(json), "{} and {} differ", colon, json);
+            assert_eq!(message_type(colon), message_type(json));
+        }
+    }
+
+    #[test]
+    fn ids_and_versions_are_optional() {
+        assert_eq!(ClientMessage::parse("deal"), Some(ClientMessage::Deal { id: None }));
+        assert_eq!(ClientMessage::parse(r#"{"type":"deal"}"#), Some(ClientMessage::Deal { id: None }));
+        assert_eq!(ClientMessage::parse("join:bob"),
+                   Some(ClientMessage::Join { name: "bob".to_string(), id: None }));
+        assert_eq!(ClientMessage::parse("state-request"),
+                   Some(ClientMessage::StateRequest { version: legacy_version() }));
+        assert_eq!(ClientMessage::parse(r#"{"type":"state-request"}"#),
+                   Some(ClientMessage::StateRequest { version: legacy_version() }));
+        assert_eq!(ClientMessage::parse("hello:3"),
+                   Some(ClientMessage::Hello { version: 3, capabilities: Vec::new() }));
+    }
+
+    #[test]
+    fn join_names_can_have_colons() {
+        assert_eq!(ClientMessage::parse("join:bob:the:builder:7"),
+                   Some(ClientMessage::Join { name: "bob:the:builder".to_string(), id: Some(7) }));
+        assert_eq!(ClientMessage::parse("join:bob:the:builder"),
+                   Some(ClientMessage::Join { name: "bob:the:builder".to_string(), id: None }));
+        assert_eq!(ClientMessage::parse("join::7"),
+                   Some(ClientMessage::Join { name: "".to_string(), id: Some(7) }));
+        assert_eq!(clean_name("bob:the,builder;"), "bobthebuilder");
+    }
+
+    #[test]
+    fn bad_messages_dont_parse() {
+        for msg in ["", "nonsense", "ack", "ack:x", "hello:x", "card:L,hearts", "{", r#"{"type":"x"}"#] {
+            assert_eq!(ClientMessage::parse(msg), None, "{} parsed", msg);
+        }
+    }
+
+    #[test]
+    fn hello_capabilities() {
+        let caps = Capabilities::from_names(["json", " patch", "someday"].into_iter());
+        assert_eq!(caps, Capabilities { json: true, table: false, patch: true });
+        // no json capability means colon messages whatever the game prefers
+        let info = ClientInfo::from_hello(3, Capabilities::default(), Encoding::Json);
+        assert_eq!(info.encoding, Encoding::Colon);
+        assert!(info.is_supported());
+        let info = ClientInfo::from_hello(3, caps, Encoding::Json);
+        assert_eq!(info.encoding, Encoding::Json);
+        // stale controller code has to reload, whether or not it says hello
+        assert!(!ClientInfo::from_hello(2, caps, Encoding::Json).is_supported());
+        assert!(!ClientInfo::from_version(3, Encoding::Json).is_supported());
+        assert!(!ClientInfo::unknown().is_supported());
+        assert_eq!(ServerMessage::Hello { accepted: false }.encode(Encoding::Colon), "hello:reload:3");
+        assert_eq!(ServerMessage::Hello { accepted: true }.encode(Encoding::Json),
+                   r#"{"type":"hello","result":"ok","version":3}"#);
+    }
+
+    #[test]
+    fn state_requests_negotiate_encoding() {
+        assert_eq!(ClientInfo::from_version(1, Encoding::Json).encoding, Encoding::Colon);
+        assert_eq!(ClientInfo::from_version(2, Encoding::Json).encoding, Encoding::Json);
+        assert_eq!(ClientInfo::from_version(2, Encoding::Colon).encoding, Encoding::Colon);
+    }
+
+    #[test]
+    fn server_messages_encode_both_ways() {
+        let cases = [
+            (ServerMessage::Joining, "state:joining", r#"{"type":"state","state":"joining"}"#),
+            (ServerMessage::Playing { view: playing_view(), seq: Some(4) },
+             "state:playing:alice:hearts,05::4",
+             r#"{"type":"state","state":"playing","seq":4,"name":"alice","left_card":"hearts,05","right_card":null}"#),
+            (ServerMessage::Patch { seq: 5, base: 4, changes: vec![("left_card", None), ("right_card", Some("clubs,K".to_string()))] },
+             "patch:5:4:left_card=;right_card=clubs,K",
+             r#"{"type":"patch","seq":5,"base":4,"changes":{"left_card":null,"right_card":"clubs,K"}}"#),
+            (ServerMessage::Accept { id: 7 }, "accept:7", r#"{"type":"accept","id":7}"#),
+            (ServerMessage::Reject { id: 8, reason: "busy" }, "reject:8:busy",
+             r#"{"type":"reject","id":8,"reason":"busy"}"#),
+            (ServerMessage::Kicked, "kicked", r#"{"type":"kicked"}"#),
+        ];
+        for (msg, colon, json) in cases {
+            assert_eq!(msg.encode(Encoding::Colon), colon);
+            assert_eq!(msg.encode(Encoding::Json), json);
+        }
+    }
+
+    #[test]
+    fn patches_only_have_changes() {
+        let base = playing_view();
+        let mut view = base.clone();
+        assert!(view.changes_from(&base).is_empty());
+        view.left_card = None;
+        view.right_card = Some("clubs,K".to_string());
+        assert_eq!(view.changes_from(&base),
+                   vec![("left_card", None), ("right_card", Some("clubs,K".to_string()))]);
+    }
+}
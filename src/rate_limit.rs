@@ -0,0 +1,269 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::protocol::message_type;
+
+// how many messages of each type a controlpad may send
+// - (message type, messages per second, burst)
+// - burst is how many can be sent at once after a quiet period
+const MESSAGE_LIMITS: &[(&str, f32, f32)] = &[
+    ("hello", 1.0, 3.0),
+    ("state-request", 2.0, 5.0),
+    ("ack", 30.0, 60.0),
+    ("join", 1.0, 3.0),
+    ("deal", 2.0, 3.0),
+    ("card", 2.0, 3.0),
+];
+// limit for message types not listed above (they all share one bucket, so
+// making up new types doesn't get a client any more messages)
+const OTHER_LIMIT: (f32, f32) = (5.0, 10.0);
+const OTHER_TYPE: &str = "other";
+// violations are counted over windows of this many ticks
+const VIOLATION_WINDOW_TICKS: u32 = 600;
+
+
+//////// TokenBucket ////////
+// holds up to `capacity` tokens and gains `per_tick` tokens every tick; every
+// message costs one token
+struct TokenBucket {
+    tokens: f32,
+    capacity: f32,
+    per_tick: f32,
+}
+
+impl TokenBucket {
+    // WARNING: assumes 60 fps
+    fn new(per_second: f32, burst: f32) -> Self {
+        Self {
+            tokens: burst,
+            capacity: burst,
+            per_tick: per_second / 60.0,
+        }
+    }
+
+    fn for_bucket_type(bucket_type: &str) -> Self {
+        let (per_second, burst) = MESSAGE_LIMITS.iter()
+            .find(|(t, _, _)| *t == bucket_type)
+            .map_or(OTHER_LIMIT, |(_, per_second, burst)| (*per_second, *burst));
+        Self::new(per_second, burst)
+    }
+
+    fn update(&mut self) {
+        self.tokens = (self.tokens + self.per_tick).min(self.capacity);
+    }
+
+    fn take(&mut self) -> bool {
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+
+// which bucket a message type is counted against
+fn bucket_type(msg_type: &str) -> &'static str {
+    MESSAGE_LIMITS.iter()
+        .find(|(t, _, _)| *t == msg_type)
+        .map_or(OTHER_TYPE, |(t, _, _)| *t)
+}
+
+
+//////// RateLimiter ////////
+#[derive(PartialEq, Debug)]
+pub enum RateDecision {
+    // handle the message
+    Allow,
+    // drop the message
+    Throttle,
+    // drop the message and remove the client from the game
+    Kick,
+}
+
+// Per-client, per-message-type limits on what controlpads can send so one
+// flooding phone (or a stuck button) can't drain the deck in a single frame
+pub struct RateLimiter {
+    buckets: HashMap<(String, &'static str), TokenBucket>,
+    // throttled messages per client in the current window
+    violations: HashMap<String, u32>,
+    window_ticks: u32,
+    // clients over this many violations in a window get kicked (None to only
+    // ever throttle)
+    kick_after: Option<u32>,
+    // (until they disconnect, e.g. by reloading the controlpad)
+    kicked: HashSet<String>,
+}
+
+impl RateLimiter {
+    pub fn new(kick_after: Option<u32>) -> Self {
+        Self {
+            buckets: HashMap::new(),
+            violations: HashMap::new(),
+            window_ticks: 0,
+            kick_after,
+            kicked: HashSet::new(),
+        }
+    }
+
+    // call once per tick
+    pub fn update(&mut self) {
+        for bucket in self.buckets.values_mut() {
+            bucket.update();
+        }
+        self.window_ticks += 1;
+        if self.window_ticks >= VIOLATION_WINDOW_TICKS {
+            self.window_ticks = 0;
+            self.violations.clear();
+        }
+    }
+
+    // forget a client's buckets, violations and kick once it's disconnected
+    // (so the handle starts over if it reconnects)
+    pub fn forget(&mut self, client: &str) {
+        self.buckets.retain(|(x, _), _| x != client);
+        self.violations.remove(client);
+        self.kicked.remove(client);
+    }
+
+    pub fn check(&mut self, client: &str, msg: &str) -> RateDecision {
+        if self.kicked.contains(client) {
+            return RateDecision::Throttle;
+        }
+        let msg_type = message_type(msg);
+        let bucket_type = bucket_type(&msg_type);
+        let bucket = self.buckets.entry((client.to_string(), bucket_type))
+            .or_insert_with(|| TokenBucket::for_bucket_type(bucket_type));
+        if bucket.take() {
+            return RateDecision::Allow;
+        }
+        let violations = self.violations.entry(client.to_string()).or_insert(0);
+        *violations += 1;
+        if *violations == 1 {
+            // only warn once per window so the log doesn't get flooded too
+            println!("WARNING: throttling {} messages from {}", &msg_type, client);
+        }
+        if let Some(kick_after) = self.kick_after {
+            if *violations > kick_after {
+                println!("WARNING: kicking {} for flooding", client);
+                self.kicked.insert(client.to_string());
+                return RateDecision::Kick;
+            }
+        }
+        RateDecision::Throttle
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // sends msg as client count times and returns the decisions
+    fn send(limiter: &mut RateLimiter, client: &str, msg: &str, count: usize) -> Vec<RateDecision> {
+        (0..count).map(|_| limiter.check(client, msg)).collect()
+    }
+
+    fn allowed(decisions: &[RateDecision]) -> usize {
+        decisions.iter().filter(|x| **x == RateDecision::Allow).count()
+    }
+
+    #[test]
+    fn buckets_allow_a_burst_then_refill() {
+        let mut bucket = TokenBucket::new(2.0, 3.0);
+        assert!((0..3).all(|_| bucket.take()));
+        assert!(!bucket.take());
+        // 2 per second is one every 30 ticks
+        for _ in 0..29 {
+            bucket.update();
+        }
+        assert!(!bucket.take());
+        bucket.update();
+        assert!(bucket.take());
+        // it never holds more than its burst
+        for _ in 0..600 {
+            bucket.update();
+        }
+        assert_eq!((0..10).filter(|_| bucket.take()).count(), 3);
+    }
+
+    #[test]
+    fn messages_over_the_limit_are_throttled() {
+        let mut limiter = RateLimiter::new(None);
+        // deal's burst is 3
+        let decisions = send(&mut limiter, "a", "deal:1", 5);
+        assert_eq!(allowed(&decisions), 3);
+        assert_eq!(decisions[3..], [RateDecision::Throttle, RateDecision::Throttle]);
+        // other types and other clients have their own buckets
+        assert_eq!(limiter.check("a", "card:L,hearts,05"), RateDecision::Allow);
+        assert_eq!(limiter.check("b", "deal"), RateDecision::Allow);
+        // and the bucket refills
+        for _ in 0..30 {
+            limiter.update();
+        }
+        assert_eq!(limiter.check("a", r#"{"type":"deal"}"#), RateDecision::Allow);
+    }
+
+    #[test]
+    fn made_up_types_share_a_bucket() {
+        let mut limiter = RateLimiter::new(None);
+        let decisions: Vec<_> = (0..100)
+            .map(|i| limiter.check("a", &format!(r#"{{"type":"x{}"}}"#, i)))
+            .collect();
+        assert_eq!(allowed(&decisions), OTHER_LIMIT.1 as usize);
+        assert_eq!(limiter.buckets.len(), 1);
+    }
+
+    #[test]
+    fn floods_get_kicked() {
+        let mut limiter = RateLimiter::new(Some(10));
+        let decisions = send(&mut limiter, "a", "deal", 3 + 11);
+        assert_eq!(allowed(&decisions), 3);
+        assert_eq!(decisions.last(), Some(&RateDecision::Kick));
+        assert!(decisions[3..13].iter().all(|x| *x == RateDecision::Throttle));
+        // everything after the kick is dropped, even once the bucket refills
+        for _ in 0..120 {
+            limiter.update();
+        }
+        assert_eq!(limiter.check("a", "deal"), RateDecision::Throttle);
+    }
+
+    #[test]
+    fn violations_are_counted_per_window() {
+        let mut limiter = RateLimiter::new(Some(10));
+        // 10 violations is as many as are allowed in one window
+        assert_eq!(allowed(&send(&mut limiter, "a", "deal", 13)), 3);
+        for _ in 0..VIOLATION_WINDOW_TICKS {
+            limiter.update();
+        }
+        // a fresh window (and a refilled bucket)
+        let decisions = send(&mut limiter, "a", "deal", 13);
+        assert_eq!(allowed(&decisions), 3);
+        assert!(!decisions.contains(&RateDecision::Kick));
+    }
+
+    #[test]
+    fn disconnected_clients_are_forgotten() {
+        let mut limiter = RateLimiter::new(Some(10));
+        send(&mut limiter, "a", "deal", 10);
+        send(&mut limiter, "a", "nonsense", 20);
+        send(&mut limiter, "b", "deal", 1);
+        limiter.forget("a");
+        assert!(limiter.buckets.keys().all(|(client, _)| client == "b"));
+        assert!(!limiter.violations.contains_key("a"));
+    }
+
+    #[test]
+    fn kicked_clients_can_reconnect() {
+        let mut limiter = RateLimiter::new(Some(10));
+        send(&mut limiter, "a", "deal", 3 + 11);
+        send(&mut limiter, "b", "deal", 3 + 11);
+        limiter.forget("a");
+        // a starts over with a full bucket and no violations, b is still kicked
+        let decisions = send(&mut limiter, "a", "deal", 3 + 10);
+        assert_eq!(allowed(&decisions), 3);
+        assert!(!decisions.contains(&RateDecision::Kick));
+        assert_eq!(limiter.check("b", "hello:3"), RateDecision::Throttle);
+        assert_eq!(limiter.kicked.len(), 1);
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{LineWriter, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::my_card_game::Difficulty;
+
+// A replay file is everything that came into the game from outside, one JSON
+// event per line, each tagged with the tick it was handled on. Since the
+// game's randomness all comes from the seed, feeding the same events to a
+// game with the same seed on the same ticks plays out the same game.
+// - {"type":"seed","seed":123} (always the first line)
+// - {"type":"connect","tick":5,"client":"192.168.1.4-1"}
+// - {"type":"disconnect","tick":900,"client":"192.168.1.4-1"}
+// - {"type":"message","tick":6,"client":"192.168.1.4-1","message":"hello:3:json"}
+// - {"type":"key-press","tick":40}
+// - {"type":"add-ai","tick":41,"difficulty":"hard"}
+// - {"type":"check","tick":600,"summary":"..."} (what the game looked like,
+//   so replays can tell if they've gone differently)
+
+// how often the recorder writes a check event
+pub const CHECK_INTERVAL_TICKS: u64 = 600;
+
+
+//////// ReplayEvent ////////
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ReplayEvent {
+    Seed { seed: u64 },
+    Connect { tick: u64, client: String },
+    Disconnect { tick: u64, client: String },
+    Message { tick: u64, client: String, message: String },
+    // a key press passed on to MyCardGame (it doesn't care which key)
+    KeyPress { tick: u64 },
+    AddAi { tick: u64, difficulty: Difficulty },
+    Check { tick: u64, summary: String },
+}
+
+impl ReplayEvent {
+    pub fn tick(&self) -> u64 {
+        use ReplayEvent::*;
+        match self {
+            Seed { .. } => 0,
+            Connect { tick, .. } | Disconnect { tick, .. } | Message { tick, .. }
+            | KeyPress { tick } | AddAi { tick, .. } | Check { tick, .. } => *tick,
+        }
+    }
+}
+
+
+//////// Recorder ////////
+pub struct Recorder {
+    // line buffered so a crash loses at most the line being written
+    file: LineWriter<File>,
+    path: String,
+}
+
+impl Recorder {
+    pub fn create(path: &str, seed: u64) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut recorder = Self {
+            file: LineWriter::new(file),
+            path: path.to_string(),
+        };
+        recorder.record(&ReplayEvent::Seed { seed });
+        Ok(recorder)
+    }
+
+    pub fn record(&mut self, event: &ReplayEvent) {
+        let line = serde_json::to_string(event).unwrap();
+        if let Err(e) = writeln!(self.file, "{}", line) {
+            println!("WARNING: failed to write to replay file {}: {}", &self.path, e);
+        }
+    }
+}
+
+
+//////// Replay ////////
+pub struct Replay {
+    seed: u64,
+    // in tick order
+    events: VecDeque<ReplayEvent>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut events = VecDeque::new();
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: ReplayEvent = serde_json::from_str(line)
+                .map_err(|e| format!("{} line {}: {}", path, i + 1, e))?;
+            events.push_back(event);
+        }
+        let seed = match events.pop_front() {
+            Some(ReplayEvent::Seed { seed }) => seed,
+            _ => return Err(format!("{}: doesn't start with a seed", path)),
+        };
+        Ok(Self { seed, events })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // the events that happened on `tick` (call with every tick in order)
+    pub fn events_for_tick(&mut self, tick: u64) -> Vec<ReplayEvent> {
+        let mut events = Vec::new();
+        while self.events.front().is_some_and(|x| x.tick() <= tick) {
+            events.push(self.events.pop_front().unwrap());
+        }
+        events
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.events.is_empty()
+    }
+}
@@ -1,38 +1,77 @@
 use std::collections::HashMap;
 
-use ggez::{graphics, Context};
+use ggez::{graphics, Context, GameResult};
 
 use crate::standard_deck::StandardDeckResources;
+use crate::theme::*;
 
 //////// GameResources ////////
 pub struct GameResources {
     pub deck_res: StandardDeckResources,
-    card_placeholder: graphics::Image,
     // text_graphics: we will store rendered text into hashmap so that we don't
     // render text every frame which is expensive
     // (I'm actually not sure if this is helpful in ggez 0.9.3 but it was in 0.7.0)
     text_graphics: HashMap<String, graphics::Text>,
+    // every theme there is (see theme.rs) and which one is in use
+    themes: Vec<Theme>,
+    theme: usize,
+    // (None if the theme's font couldn't be loaded, or it doesn't have one)
+    font: Option<String>,
 }
 
 
 impl GameResources {
-    pub fn new(ctx: &mut Context) -> Self {
-        Self {
-            deck_res: StandardDeckResources::new(ctx),
-            card_placeholder: graphics::Image::from_path(ctx, "/card_none.png").unwrap(),
+    // (starts with the theme called theme_name, or the classic theme)
+    pub fn new(ctx: &mut Context, theme_name: Option<&str>) -> GameResult<Self> {
+        let themes = load_themes(ctx);
+        let theme = match theme_name {
+            Some(name) => themes.iter().position(|x| x.name == name).unwrap_or_else(|| {
+                println!("WARNING: no theme called {}, using {}", name, themes[0].name);
+                0
+            }),
+            None => 0,
+        };
+        let deck_res = StandardDeckResources::new(ctx, &themes[theme])?;
+        let font = load_font(ctx, &themes[theme]);
+        Ok(Self {
+            deck_res,
             text_graphics: HashMap::new(),
-        }
+            themes,
+            theme,
+            font,
+        })
     }
-        
-    pub fn get_placeholder(&self) -> &graphics::Image {
-        &self.card_placeholder
+
+    pub fn theme(&self) -> &Theme {
+        &self.themes[self.theme]
     }
 
+    // switch to the next theme (back to the first after the last)
+    pub fn next_theme(&mut self, ctx: &mut Context) {
+        let next = (self.theme + 1) % self.themes.len();
+        let theme = &self.themes[next];
+        match StandardDeckResources::new(ctx, theme) {
+            Ok(deck_res) => self.deck_res = deck_res,
+            Err(e) => {
+                println!("WARNING: couldn't switch to theme {}: {}", theme.name, e);
+                return;
+            }
+        }
+        println!("theme: {}", theme.name);
+        self.theme = next;
+        self.font = load_font(ctx, theme);
+        // (the old text was rendered with the old font)
+        self.text_graphics.clear();
+    }
+        
     pub fn get_text_graphic(&mut self, text: &str) -> &graphics::Text {
         if self.text_graphics.get(text).is_none() {
-            let fragment = graphics::TextFragment::new(text)
+            let mut fragment = graphics::TextFragment::new(text)
                 .color((0.0, 0.0, 0.0))
                 .scale(32.0);
+            if let Some(font) = &self.font {
+                fragment = fragment.font(font);
+            }
             let new_graphic = graphics::Text::new(fragment);
             self.text_graphics.insert(text.to_string(), new_graphic);
         } 
@@ -40,3 +79,18 @@ impl GameResources {
     }
 
 }
+
+// adds theme's font to ggez (named after its path) and returns its name
+fn load_font(ctx: &mut Context, theme: &Theme) -> Option<String> {
+    let path = theme.font.as_ref()?;
+    match graphics::FontData::from_path(ctx, path) {
+        Ok(font_data) => {
+            ctx.gfx.add_font(path, font_data);
+            Some(path.clone())
+        }
+        Err(e) => {
+            println!("WARNING: couldn't load font {}: {}", path, e);
+            None
+        }
+    }
+}
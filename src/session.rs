@@ -0,0 +1,367 @@
+use rand::Rng;
+use rand::thread_rng;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+
+use crate::bots::*;
+use crate::my_card_game::*;
+use crate::rate_limit::*;
+use crate::replay::*;
+use crate::transport;
+use crate::{Options, KICK_AFTER_VIOLATIONS};
+
+// live games are saved here every AUTOSAVE_TICKS so they can be resumed (with
+// --resume) if the game restarts
+const AUTOSAVE_FILE: &str = "autosave.json";
+const AUTOSAVE_TICKS: u64 = 300;
+// a new game (one that wasn't resumed) moves the last game's save here before
+// its first autosave, so starting without --resume by mistake doesn't lose it
+const PREVIOUS_AUTOSAVE_FILE: &str = "autosave.previous.json";
+
+// Everything the game does each tick apart from drawing: gathering what came
+// in from outside (controlpads, key presses) as ReplayEvents, handing them to
+// MyCardGame and updating it. Live games get their events from the transport
+// (and can record them); replays get them from a replay file. Since it doesn't
+// need a window, replays can also be run headless.
+
+
+//////// GameSession ////////
+pub struct GameSession {
+    pub card_game: MyCardGame,
+    client_handles: Vec<String>,
+    rate_limiter: RateLimiter,
+    bots: BotManager,
+    bot_strategy: String,
+    ai_difficulty: Difficulty,
+    tick: u64,
+    recorder: Option<Recorder>,
+    replay: Option<Replay>,
+    // key presses waiting to be handled on the next tick
+    pending_events: Vec<ReplayEvent>,
+    // checks in the replay that didn't match
+    failed_checks: u32,
+    // (with --log-events) everything that happens in the game gets printed
+    event_log: Option<Receiver<GameEvent>>,
+    // (replays don't overwrite the live game's save)
+    autosaving: bool,
+    // whether AUTOSAVE_FILE is another game's save that has to be moved out of
+    // the way before autosaving
+    move_old_save: bool,
+}
+
+impl GameSession {
+    pub fn new(options: &Options) -> Result<Self, String> {
+        let replay = match &options.replay {
+            Some(path) => Some(Replay::load(path)?),
+            None => None,
+        };
+        let seed = replay.as_ref().map_or_else(|| thread_rng().gen(), |x| x.seed());
+        let recorder = match (&options.record, &replay) {
+            (Some(path), None) => Some(Recorder::create(path, seed)?),
+            (Some(_), Some(_)) => return Err("can't record while replaying".to_string()),
+            _ => None,
+        };
+        let mut card_game = match (options.resume, &replay) {
+            (false, _) => MyCardGame::new(seed),
+            (true, None) if recorder.is_none() => resume(seed)?,
+            // a replay has to start from a new game to play out the same way
+            (true, _) => return Err("can't resume while recording or replaying".to_string()),
+        };
+        let event_log = options.log_events.then(|| card_game.subscribe());
+        let replaying = replay.is_some();
+        let mut session = Self {
+            card_game,
+            client_handles: Vec::new(),
+            rate_limiter: RateLimiter::new(KICK_AFTER_VIOLATIONS),
+            bots: BotManager::new(),
+            bot_strategy: options.bot_strategy.clone(),
+            ai_difficulty: options.ai_difficulty,
+            tick: 0,
+            recorder,
+            replay,
+            pending_events: Vec::new(),
+            failed_checks: 0,
+            event_log,
+            autosaving: !replaying,
+            move_old_save: !options.resume,
+        };
+        // (a replay already has whatever the bots and AI players did)
+        if session.replay.is_none() {
+            for _ in 0..options.bots {
+                session.spawn_bot();
+            }
+            for _ in 0..options.ai_players {
+                session.add_ai_player();
+            }
+        }
+        Ok(session)
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    pub fn replay_done(&self) -> bool {
+        self.replay.as_ref().is_some_and(|x| x.is_done())
+    }
+
+    pub fn failed_checks(&self) -> u32 {
+        self.failed_checks
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn spawn_bot(&mut self) {
+        if let Some(strategy) = strategy_from_str(&self.bot_strategy) {
+            self.bots.spawn(strategy);
+        } else {
+            println!("WARNING: bad bot strategy: {}", &self.bot_strategy);
+        }
+    }
+
+    // handled on the next tick (so it's recorded with the tick it happens on)
+    pub fn key_press(&mut self) {
+        self.pending_events.push(ReplayEvent::KeyPress { tick: self.tick + 1 });
+    }
+
+    pub fn add_ai_player(&mut self) {
+        let difficulty = self.ai_difficulty;
+        self.pending_events.push(ReplayEvent::AddAi { tick: self.tick + 1, difficulty });
+    }
+
+    // call once per tick
+    pub fn update(&mut self) {
+        self.tick += 1;
+        self.rate_limiter.update();
+        let events = match &mut self.replay {
+            Some(replay) => replay.events_for_tick(self.tick),
+            None => self.poll_events(),
+        };
+        // checks describe the game after the tick's update
+        let (checks, events): (Vec<_>, Vec<_>) = events.into_iter()
+            .partition(|x| matches!(x, ReplayEvent::Check { .. }));
+        for event in events {
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record(&event);
+            }
+            self.handle_event(event);
+        }
+        self.card_game.update();
+        if let Some(event_log) = &self.event_log {
+            for event in event_log.try_iter() {
+                println!("tick {}: {}", self.tick, event);
+            }
+        }
+        // let bots react to what the game sent them
+        self.bots.update();
+        for check in checks {
+            self.verify(check);
+        }
+        if self.autosaving && self.tick.is_multiple_of(AUTOSAVE_TICKS) {
+            self.autosave();
+        }
+        if let Some(recorder) = &mut self.recorder {
+            if self.tick.is_multiple_of(CHECK_INTERVAL_TICKS) {
+                let summary = self.card_game.replay_summary();
+                recorder.record(&ReplayEvent::Check { tick: self.tick, summary });
+            }
+        }
+    }
+
+    fn autosave(&mut self) {
+        if self.move_old_save {
+            if let Err(e) = move_old_save() {
+                println!("WARNING: not autosaving (so the last game's save isn't lost): {}", e);
+                self.autosaving = false;
+                return;
+            }
+            self.move_old_save = false;
+        }
+        if let Err(e) = self.card_game.snapshot().save(AUTOSAVE_FILE) {
+            println!("WARNING: autosave failed: {}", e);
+        }
+    }
+
+    // what came in from outside the game this tick
+    fn poll_events(&mut self) -> Vec<ReplayEvent> {
+        let tick = self.tick;
+        let mut events: Vec<ReplayEvent> = self.pending_events.drain(..).collect();
+        if let Ok(true) = transport::clients_changed() {
+            if let Ok(handles) = transport::get_client_handles() {
+                for client in self.client_handles.iter().filter(|x| !handles.contains(x)) {
+                    events.push(ReplayEvent::Disconnect { tick, client: client.clone() });
+                }
+                for client in handles.iter().filter(|x| !self.client_handles.contains(x)) {
+                    events.push(ReplayEvent::Connect { tick, client: client.clone() });
+                }
+                self.client_handles = handles;
+            } else {
+                println!("Warning: Failed to get client handles");
+            }
+        }
+        for client in &self.client_handles {
+            if let Ok(msgs) = transport::get_messages(client) {
+                for message in msgs {
+                    events.push(ReplayEvent::Message { tick, client: client.clone(), message });
+                }
+            } else {
+                println!("WARNING: Error while gatting controlad messages");
+            }
+        }
+        events
+    }
+
+    fn handle_event(&mut self, event: ReplayEvent) {
+        match event {
+            ReplayEvent::Connect { client, .. } => {
+                if !self.client_handles.contains(&client) {
+                    self.client_handles.push(client);
+                }
+            }
+            ReplayEvent::Disconnect { client, .. } => {
+                self.rate_limiter.forget(&client);
+                self.client_handles.retain(|x| *x != client);
+            }
+            // handle received controlpad messages (unless a controlpad is
+            // sending more than its fair share)
+            ReplayEvent::Message { client, message, .. } => {
+                match self.rate_limiter.check(&client, &message) {
+                    RateDecision::Allow => self.card_game.handle_controlpad_message(client, message),
+                    RateDecision::Throttle => (),
+                    RateDecision::Kick => self.card_game.kick(&client),
+                }
+            }
+            ReplayEvent::KeyPress { .. } => self.card_game.handle_key_press(),
+            ReplayEvent::AddAi { difficulty, .. } => {
+                if !self.card_game.add_ai_player(difficulty) {
+                    println!("WARNING: no empty seat for an AI player");
+                }
+            }
+            ReplayEvent::Seed { .. } | ReplayEvent::Check { .. } => (),
+        }
+    }
+
+    fn verify(&mut self, check: ReplayEvent) {
+        if let ReplayEvent::Check { tick, summary } = check {
+            let actual = self.card_game.replay_summary();
+            if actual != summary {
+                println!("WARNING: replay differs from the recording at tick {}", tick);
+                println!("  recorded: {}", summary);
+                println!("  replayed: {}", actual);
+                self.failed_checks += 1;
+            }
+        }
+    }
+}
+
+
+//////// Resuming ////////
+fn move_old_save() -> Result<(), String> {
+    if !Path::new(AUTOSAVE_FILE).exists() {
+        return Ok(());
+    }
+    fs::rename(AUTOSAVE_FILE, PREVIOUS_AUTOSAVE_FILE)
+        .map_err(|e| format!("couldn't move {} to {}: {}", AUTOSAVE_FILE, PREVIOUS_AUTOSAVE_FILE, e))?;
+    println!("moved the last game's save to {}", PREVIOUS_AUTOSAVE_FILE);
+    Ok(())
+}
+
+fn resume(seed: u64) -> Result<MyCardGame, String> {
+    let snapshot = GameSnapshot::load(AUTOSAVE_FILE)?;
+    println!("resuming the game saved in {}", AUTOSAVE_FILE);
+    MyCardGame::from_snapshot(&snapshot, seed)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::local;
+
+    const SCRIPT_TICKS: u64 = 3 * CHECK_INTERVAL_TICKS;
+
+    // two controlpads join and deal now and then while two AI players play
+    fn script(clients: &[&str]) -> Vec<ReplayEvent> {
+        let mut events = vec![ReplayEvent::AddAi { tick: 1, difficulty: Difficulty::Easy },
+                              ReplayEvent::AddAi { tick: 2, difficulty: Difficulty::Hard }];
+        for (i, client) in clients.iter().enumerate() {
+            let tick = 10 + i as u64;
+            let message = |message: &str| ReplayEvent::Message {
+                tick, client: client.to_string(), message: message.to_string(),
+            };
+            events.push(ReplayEvent::Connect { tick, client: client.to_string() });
+            events.push(message("hello:3:table"));
+            events.push(message(&format!("join:player{}:1", i)));
+        }
+        for tick in (100..SCRIPT_TICKS).step_by(250) {
+            events.push(ReplayEvent::Message {
+                tick, client: clients[0].to_string(), message: format!("deal:{}", tick),
+            });
+            events.push(ReplayEvent::KeyPress { tick: tick + 1 });
+        }
+        events
+    }
+
+    fn write_replay(path: &str, seed: u64, events: &[ReplayEvent]) {
+        let mut recorder = Recorder::create(path, seed).unwrap();
+        for event in events {
+            recorder.record(event);
+        }
+    }
+
+    fn replay(path: &str) -> GameSession {
+        let options = Options { replay: Some(path.to_string()), ..Options::default() };
+        let mut session = GameSession::new(&options).unwrap();
+        while !session.replay_done() || session.tick() < SCRIPT_TICKS {
+            session.update();
+        }
+        session
+    }
+
+    #[test]
+    fn replays_play_out_the_same() {
+        let clients = ["test-replay-1", "test-replay-2"];
+        for client in clients {
+            local::connect(client);
+        }
+        let path = std::env::temp_dir().join(format!("replay-test-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let seed = 1234;
+        let events = script(&clients);
+        // play the script once and note what the game looked like every
+        // CHECK_INTERVAL_TICKS, the way the recorder does
+        write_replay(path, seed, &events);
+        let options = Options { replay: Some(path.to_string()), ..Options::default() };
+        let mut session = GameSession::new(&options).unwrap();
+        let mut checks = Vec::new();
+        while session.tick() < SCRIPT_TICKS {
+            session.update();
+            if session.tick().is_multiple_of(CHECK_INTERVAL_TICKS) {
+                let summary = session.card_game.replay_summary();
+                checks.push(ReplayEvent::Check { tick: session.tick(), summary });
+            }
+        }
+        assert_eq!(checks.len(), 3);
+        // then play it again with the checks in it
+        let mut recording = events.clone();
+        recording.extend(checks.iter().cloned());
+        recording.sort_by_key(|x| x.tick());
+        write_replay(path, seed, &recording);
+        assert_eq!(replay(path).failed_checks(), 0);
+        // (and a check that doesn't match is noticed)
+        if let Some(ReplayEvent::Check { summary, .. }) = recording.iter_mut().rev()
+            .find(|x| matches!(x, ReplayEvent::Check { .. })) {
+            summary.push('!');
+        }
+        write_replay(path, seed, &recording);
+        assert_eq!(replay(path).failed_checks(), 1);
+        std::fs::remove_file(path).ok();
+        for client in clients {
+            local::disconnect(client);
+        }
+    }
+}
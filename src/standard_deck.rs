@@ -1,8 +1,18 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 use CardSuit::*;
 use CardRank::*;
-use ggez::{Context, graphics};
+use ggez::{Context, GameResult, graphics};
+use glam::Vec2;
+
+use crate::procedural_cards::procedural_card;
+use crate::theme::Theme;
+
+// how big the card images are (in pixels)
+pub const CARD_IMG_WIDTH: f32 = 148.0;
+pub const CARD_IMG_HEIGHT: f32 = 148.0;
 
 pub const CARD_SUITS: [CardSuit; 4] = [ Heart, Diamond, Spade, Club ];
 pub const CARD_RANKS: [CardRank; 13] = [ _02, _03, _04, _05, _06, _07, _08, _09,
@@ -18,13 +28,13 @@ pub enum CardSuit {
 }
 
 impl CardSuit {
-    pub fn from_str(s: &str) -> Self {
+    pub fn try_from_str(s: &str) -> Option<Self> {
         match s {
-            "hearts" => Heart,
-            "diamonds" => Diamond,
-            "spades" => Spade,
-            "clubs" => Club,
-            _ => panic!("bad suit: {}", s)
+            "hearts" => Some(Heart),
+            "diamonds" => Some(Diamond),
+            "spades" => Some(Spade),
+            "clubs" => Some(Club),
+            _ => None,
         }
     }
 
@@ -57,22 +67,22 @@ pub enum CardRank {
 }
 
 impl CardRank {
-    pub fn from_str(s: &str) -> Self {
+    pub fn try_from_str(s: &str) -> Option<Self> {
         match s {
-            "02" => _02,
-            "03" => _03,
-            "04" => _04,
-            "05" => _05,
-            "06" => _06,
-            "07" => _07,
-            "08" => _08,
-            "09" => _09,
-            "10" => _10,
-            "J" => _J,
-            "Q" => _Q,
-            "K" => _K,
-            "A" => _A,
-            _ => panic!("bad rank: {}", s)
+            "02" => Some(_02),
+            "03" => Some(_03),
+            "04" => Some(_04),
+            "05" => Some(_05),
+            "06" => Some(_06),
+            "07" => Some(_07),
+            "08" => Some(_08),
+            "09" => Some(_09),
+            "10" => Some(_10),
+            "J" => Some(_J),
+            "Q" => Some(_Q),
+            "K" => Some(_K),
+            "A" => Some(_A),
+            _ => None,
         }
     }
 
@@ -93,6 +103,11 @@ impl CardRank {
             _A => "A",
         }
     }
+
+    // 2 through 10 for number cards, then 11 (jack) through 14 (ace)
+    pub fn value(&self) -> u32 {
+        CARD_RANKS.iter().position(|x| x == self).unwrap() as u32 + 2
+    }
 }
 
 //////// CardSpec ////////
@@ -103,52 +118,324 @@ pub struct CardSpec {
 }
 impl CardSpec {
     pub fn from_strs(suit: &str, rank: &str) -> Self {
-        Self {
-            suit: CardSuit::from_str(suit),
-            rank: CardRank::from_str(rank),
+        Self::try_from_strs(suit, rank).unwrap_or_else(|| panic!("bad card: {},{}", suit, rank))
+    }
+
+    // like from_strs but for strings that came from outside the game
+    pub fn try_from_strs(suit: &str, rank: &str) -> Option<Self> {
+        Some(Self {
+            suit: CardSuit::try_from_str(suit)?,
+            rank: CardRank::try_from_str(rank)?,
+        })
+    }
+
+    pub fn suit(&self) -> CardSuit {
+        self.suit
+    }
+
+    pub fn rank(&self) -> CardRank {
+        self.rank
+    }
+}
+
+
+//////// Display / FromStr ////////
+// the way cards are written in the protocol: suits like "hearts", ranks like
+// "05" or "K" and cards like "hearts,05"
+impl fmt::Display for CardSuit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl fmt::Display for CardRank {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl fmt::Display for CardSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.suit, self.rank)
+    }
+}
+
+impl FromStr for CardSuit {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from_str(s).ok_or(format!("bad suit: {}", s))
+    }
+}
+
+impl FromStr for CardRank {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from_str(s).ok_or(format!("bad rank: {}", s))
+    }
+}
+
+impl FromStr for CardSpec {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (suit, rank) = s.split_once(",").ok_or(format!("bad card: {}", s))?;
+        Ok(Self {
+            suit: suit.parse()?,
+            rank: rank.parse()?,
+        })
+    }
+}
+
+
+//////// Serde ////////
+// cards are serialized as the same strings Display/FromStr use, e.g.
+// "hearts,05"
+macro_rules! serde_as_string {
+    ($t:ty) => {
+        impl serde::Serialize for $t {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
         }
+
+        impl<'de> serde::Deserialize<'de> for $t {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let s = String::deserialize(deserializer)?;
+                s.parse().map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+serde_as_string!(CardSuit);
+serde_as_string!(CardRank);
+serde_as_string!(CardSpec);
+
+
+//////// CardAtlas ////////
+// which of a card's images to draw
+#[derive(PartialEq, Clone, Copy)]
+pub enum CardFace {
+    Front(CardSpec),
+    Back,
+    // (drawn where the deck is when it's empty)
+    Placeholder,
+}
+
+// how many card images go in each row of the atlas, and the gap around each
+// one (so the edges of one card don't bleed into the next)
+const ATLAS_COLUMNS: u32 = 8;
+const ATLAS_PADDING: u32 = 2;
+
+// Every card image (the 52 fronts, the back and the placeholder) packed into
+// one image when the game loads, so lots of cards can be drawn with one draw
+// call (see card_sprites.rs).
+// - images can be any size (e.g. a high-res theme's), they're all fit into
+//   the biggest one's size (keeping their shape, centered) and drawn scaled
+//   to CARD_IMG_WIDTH x CARD_IMG_HEIGHT, so a theme changes how the cards look
+//   but not how much room they take up on the table
+pub struct CardAtlas {
+    image: graphics::Image,
+    // how big every card image is in the atlas (in the atlas's pixels)
+    image_size: Vec2,
+    // where each image is in the atlas (as fractions of the atlas's size, the
+    // way DrawParam::src wants them)
+    fronts: HashMap<CardSpec, graphics::Rect>,
+    back: graphics::Rect,
+    placeholder: graphics::Rect,
+}
+
+impl CardAtlas {
+    fn new(ctx: &mut Context, images: Vec<(CardFace, graphics::Image)>) -> Self {
+        let card_width = images.iter().map(|(_, x)| x.width()).max().unwrap_or(1);
+        let card_height = images.iter().map(|(_, x)| x.height()).max().unwrap_or(1);
+        let image_size = Vec2::new(card_width as f32, card_height as f32);
+        let (tile_width, tile_height) = (card_width + 2*ATLAS_PADDING, card_height + 2*ATLAS_PADDING);
+        let rows = (images.len() as u32).div_ceil(ATLAS_COLUMNS);
+        let (width, height) = (ATLAS_COLUMNS * tile_width, rows * tile_height);
+        let image = graphics::Image::new_canvas_image(ctx, ctx.gfx.surface_format(), width, height, 1);
+        let mut canvas = graphics::Canvas::from_image(ctx, image.clone(), graphics::Color::new(0.0, 0.0, 0.0, 0.0));
+        let mut atlas = Self {
+            image,
+            image_size,
+            fronts: HashMap::new(),
+            back: graphics::Rect::default(),
+            placeholder: graphics::Rect::default(),
+        };
+        for (i, (face, card_image)) in images.iter().enumerate() {
+            let i = i as u32;
+            let x = (i % ATLAS_COLUMNS) * tile_width + ATLAS_PADDING;
+            let y = (i / ATLAS_COLUMNS) * tile_height + ATLAS_PADDING;
+            let tile_loc = Vec2::new(x as f32, y as f32);
+            let card_image_size = Vec2::new(card_image.width() as f32, card_image.height() as f32);
+            let scale = (image_size / card_image_size).min_element();
+            canvas.set_sampler(sampler_for_scale(scale));
+            let dest = tile_loc + 0.5 * (image_size - scale * card_image_size);
+            canvas.draw(card_image, graphics::DrawParam::default().dest(dest).scale(Vec2::splat(scale)));
+            let src = graphics::Rect::new(x as f32 / width as f32, y as f32 / height as f32,
+                                          image_size.x / width as f32, image_size.y / height as f32);
+            match face {
+                CardFace::Front(card) => { atlas.fronts.insert(*card, src); }
+                CardFace::Back => atlas.back = src,
+                CardFace::Placeholder => atlas.placeholder = src,
+            }
+        }
+        if let Err(e) = canvas.finish(ctx) {
+            println!("WARNING: couldn't draw the card atlas: {}", e);
+        }
+        atlas
+    }
+
+    pub fn image(&self) -> &graphics::Image {
+        &self.image
     }
 
-    pub fn to_string(&self) -> String {
-        format!("{},{}", self.suit.to_str(), self.rank.to_str())
+    // how big cards are on the table (whatever size the images are)
+    pub fn card_size(&self) -> Vec2 {
+        Vec2::new(CARD_IMG_WIDTH, CARD_IMG_HEIGHT)
+    }
+
+    // how much to scale an image from the atlas to draw it card_size() big
+    pub fn image_scale(&self) -> Vec2 {
+        self.card_size() / self.image_size
+    }
+
+    // the sampler to draw the atlas's images with
+    pub fn sampler(&self) -> graphics::Sampler {
+        sampler_for_scale(self.image_scale().min_element())
+    }
+
+    pub fn src(&self, face: CardFace) -> graphics::Rect {
+        match face {
+            // (every front is in the atlas, but just in case)
+            CardFace::Front(card) => self.fronts.get(&card).copied().unwrap_or(self.placeholder),
+            CardFace::Back => self.back,
+            CardFace::Placeholder => self.placeholder,
+        }
+    }
+}
+
+
+// pixel art stays pixely when it's scaled up; big images are smoothed when
+// they're scaled down
+fn sampler_for_scale(scale: f32) -> graphics::Sampler {
+    if scale >= 1.0 {
+        graphics::Sampler::nearest_clamp()
+    } else {
+        graphics::Sampler::linear_clamp()
     }
 }
 
 
 //////// StandardDeckResources ////////
 pub struct StandardDeckResources {
-    card_fronts: HashMap<CardSpec, graphics::Image>,
-    card_back: graphics::Image,
+    atlas: CardAtlas,
 }
 
 impl StandardDeckResources {
-    pub fn new(ctx: &mut Context) -> Self {
-        Self {
-            card_fronts: load_card_fronts(ctx),
-            card_back: graphics::Image::from_path(ctx, "/card_back.png").unwrap(),
+    // (the card images are wherever theme says they are, see theme.rs; any
+    // that can't be loaded are drawn plainly instead, see procedural_cards.rs)
+    pub fn new(ctx: &mut Context, theme: &Theme) -> GameResult<Self> {
+        let mut faces: Vec<(CardFace, String)> = Vec::new();
+        for suit in CARD_SUITS.iter().map(|x| x.to_str()) {
+            for rank in CARD_RANKS.iter().map(|x| x.to_str()) {
+                faces.push((CardFace::Front(CardSpec::from_strs(suit, rank)), theme.front_path(suit, rank)));
+            }
+        }
+        faces.push((CardFace::Back, theme.back.clone()));
+        faces.push((CardFace::Placeholder, theme.placeholder.clone()));
+        let loaded: Vec<_> = faces.into_iter().map(|(face, path)| (face, load_image(ctx, &path))).collect();
+        // plain cards are drawn as big as the theme's images (so they're as
+        // sharp as the rest of the deck when the atlas scales them)
+        let mut plain_size = Vec2::ZERO;
+        for image in loaded.iter().filter_map(|(_, x)| x.as_ref().ok()) {
+            plain_size = plain_size.max(Vec2::new(image.width() as f32, image.height() as f32));
+        }
+        if plain_size == Vec2::ZERO {
+            plain_size = Vec2::new(CARD_IMG_WIDTH, CARD_IMG_HEIGHT);
+        }
+        let mut images = Vec::new();
+        let mut missing = Vec::new();
+        for (face, image) in loaded {
+            match image {
+                Ok(image) => images.push((face, image)),
+                Err(e) => {
+                    missing.push(e);
+                    images.push((face, procedural_card(ctx, face, plain_size)?));
+                }
+            }
         }
+        if !missing.is_empty() {
+            println!("WARNING: couldn't load {} card images, drawing them plainly instead:", missing.len());
+            for e in &missing {
+                println!("  {}", e);
+            }
+        }
+        Ok(Self {
+            atlas: CardAtlas::new(ctx, images),
+        })
     }
 
-    pub fn get_card_image(&self, card: &CardSpec) -> &graphics::Image {
-        self.card_fronts.get(card).unwrap()
+    pub fn atlas(&self) -> &CardAtlas {
+        &self.atlas
     }
+}
 
-    pub fn get_back_image(&self) -> &graphics::Image {
-        &self.card_back
-    }
+// (the error says which path it was)
+fn load_image(ctx: &mut Context, path: &str) -> Result<graphics::Image, String> {
+    graphics::Image::from_path(ctx, path).map_err(|e| format!("{}: {}", path, e))
 }
 
-fn load_card_fronts(ctx: &mut Context) -> HashMap<CardSpec, graphics::Image> {
-    let mut img_map: HashMap<CardSpec, graphics::Image> = HashMap::new();
-    // loop through every suit+rank combo and load that image from resources/card_fronts/
-    for suit in CARD_SUITS.iter().map(|x| x.to_str()) {
-        for rank in CARD_RANKS.iter().map(|x| x.to_str()) {
-            let card_spec = CardSpec::from_strs(suit, rank);
-            let img_path = format!("/card_fronts/card_{}_{}.png", suit, rank);
-            // panic if we fail to load any image
-            let img = graphics::Image::from_path(ctx, &img_path).unwrap();
-            img_map.insert(card_spec, img);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_cards() -> Vec<CardSpec> {
+        let mut cards = Vec::new();
+        for suit in CARD_SUITS {
+            for rank in CARD_RANKS {
+                cards.push(CardSpec { suit, rank });
+            }
+        }
+        cards
+    }
+
+    #[test]
+    fn cards_are_written_the_protocol_way() {
+        let card = CardSpec::from_strs("hearts", "05");
+        assert_eq!(card.to_string(), "hearts,05");
+        assert_eq!(CardSpec::from_strs("clubs", "J").to_string(), "clubs,J");
+        assert_eq!(card.suit().to_string(), "hearts");
+        assert_eq!(card.rank().to_string(), "05");
+        assert_eq!(serde_json::to_string(&card).unwrap(), r#""hearts,05""#);
+        assert_eq!(serde_json::to_string(&[card.suit()]).unwrap(), r#"["hearts"]"#);
+        assert_eq!(serde_json::to_string(&Some(card.rank())).unwrap(), r#""05""#);
+    }
+
+    #[test]
+    fn cards_round_trip() {
+        for card in all_cards() {
+            let written = card.to_string();
+            assert!(written.parse::<CardSpec>() == Ok(card), "{} didn't parse", written);
+            assert!(card.suit().to_string().parse::<CardSuit>() == Ok(card.suit()));
+            assert!(card.rank().to_string().parse::<CardRank>() == Ok(card.rank()));
+            // (serde writes cards the same way)
+            let json = serde_json::to_string(&card).unwrap();
+            assert_eq!(json, format!("\"{}\"", written));
+            assert!(serde_json::from_str::<CardSpec>(&json).unwrap() == card, "{} didn't deserialize", json);
+            let json = serde_json::to_string(&(card.suit(), card.rank())).unwrap();
+            assert!(serde_json::from_str::<(CardSuit, CardRank)>(&json).unwrap() == (card.suit(), card.rank()));
+        }
+    }
+
+    #[test]
+    fn bad_cards_dont_parse() {
+        for s in ["", "hearts", "hearts,", ",05", "hearts,5", "heart,05", "hearts,05,", "hearts;05", "HEARTS,05"] {
+            assert!(s.parse::<CardSpec>().is_err(), "{} parsed", s);
+            assert!(serde_json::from_str::<CardSpec>(&format!("\"{}\"", s)).is_err(), "{} deserialized", s);
         }
+        assert!(serde_json::from_str::<CardSpec>("5").is_err());
+        assert!("11".parse::<CardRank>().is_err());
+        assert!("club".parse::<CardSuit>().is_err());
     }
-    img_map
 }
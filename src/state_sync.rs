@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+
+use crate::protocol::*;
+
+// if a controlpad falls this many states behind on its acks we stop patching
+// and send it full states until it catches up
+const MAX_UNACKED_STATES: usize = 16;
+// how long to wait for an ack before resending (doubles with every retry up
+// to the max)
+const RETRY_TICKS: u32 = 30;
+const MAX_RETRY_TICKS: u32 = 240;
+
+
+//////// StateSync ////////
+// Keeps track of which player states a controlpad has acknowledged so that we
+// only need to send it what changed since then. Every state we send gets a
+// sequence number (seq); patches name the acked state they apply to (base) so
+// a controlpad that is missing that state knows it needs a full resync.
+pub struct StateSync {
+    next_seq: u32,
+    // states we sent that haven't been acked yet (oldest first)
+    unacked: VecDeque<(u32, PlayerView)>,
+    // the newest state the controlpad acked
+    acked: Option<(u32, PlayerView)>,
+    // ticks since we last sent a state and how long to wait for its ack
+    ticks_since_sent: u32,
+    retry_ticks: u32,
+}
+
+impl StateSync {
+    pub fn new() -> Self {
+        Self {
+            next_seq: 1,
+            unacked: VecDeque::new(),
+            acked: None,
+            ticks_since_sent: 0,
+            retry_ticks: RETRY_TICKS,
+        }
+    }
+
+    // forget what the controlpad has so the next message is a full state
+    pub fn resync(&mut self) {
+        self.unacked.clear();
+        self.acked = None;
+    }
+
+    // the message that brings a controlpad up to date with `view`
+    // - controlpads that can't patch always get the full state (without a seq)
+    pub fn next_message(&mut self, view: PlayerView, can_patch: bool) -> ServerMessage {
+        if !can_patch {
+            return ServerMessage::Playing { view, seq: None };
+        }
+        if self.unacked.len() >= MAX_UNACKED_STATES {
+            self.resync();
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.unacked.push_back((seq, view.clone()));
+        self.ticks_since_sent = 0;
+        match &self.acked {
+            Some((base, base_view)) => ServerMessage::Patch {
+                seq,
+                base: *base,
+                changes: view.changes_from(base_view),
+            },
+            None => ServerMessage::Playing { view, seq: Some(seq) },
+        }
+    }
+
+    // returns false if the ack is for a state we never sent, in which case
+    // the controlpad and the game disagree and the caller should resync
+    pub fn ack(&mut self, seq: u32) -> bool {
+        if let Some(i) = self.unacked.iter().position(|(s, _)| *s == seq) {
+            // everything sent before this state is superseded by it
+            self.acked = self.unacked.drain(..=i).next_back();
+            self.retry_ticks = RETRY_TICKS;
+            return true;
+        }
+        // a late ack for a state that was already superseded (or forgotten by
+        // a resync) is harmless
+        seq < self.next_seq
+    }
+
+    // whether the controlpad has acked everything we've sent it
+    pub fn is_synced(&self) -> bool {
+        self.unacked.is_empty()
+    }
+
+    // call once per tick; if the controlpad has gone too long without acking
+    // what we sent, returns a message to send it again (under a new seq)
+    pub fn update(&mut self) -> Option<ServerMessage> {
+        let (_, newest) = self.unacked.back()?;
+        self.ticks_since_sent += 1;
+        if self.ticks_since_sent < self.retry_ticks {
+            return None;
+        }
+        let newest = newest.clone();
+        // back off in case the controlpad is gone rather than just slow
+        self.retry_ticks = (self.retry_ticks * 2).min(MAX_RETRY_TICKS);
+        Some(self.next_message(newest, true))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(left_card: &str) -> PlayerView {
+        PlayerView {
+            name: "alice".to_string(),
+            left_card: Some(left_card.to_string()),
+            right_card: Some("spades,K".to_string()),
+        }
+    }
+
+    // (the colon encoding is the easiest way to compare messages)
+    fn send(sync: &mut StateSync, left_card: &str) -> String {
+        sync.next_message(view(left_card), true).encode(Encoding::Colon)
+    }
+
+    #[test]
+    fn acked_states_are_patched() {
+        let mut sync = StateSync::new();
+        assert_eq!(send(&mut sync, "hearts,05"), "state:playing:alice:hearts,05:spades,K:1");
+        assert!(!sync.is_synced());
+        assert!(sync.ack(1));
+        assert!(sync.is_synced());
+        // only what changed since the acked state
+        assert_eq!(send(&mut sync, "clubs,02"), "patch:2:1:left_card=clubs,02");
+    }
+
+    #[test]
+    fn acks_supersede_older_states() {
+        let mut sync = StateSync::new();
+        send(&mut sync, "hearts,05");
+        send(&mut sync, "hearts,06");
+        send(&mut sync, "hearts,07");
+        assert!(sync.ack(2));
+        // state 1 was superseded by 2, so its late ack is harmless
+        assert!(sync.ack(1));
+        assert!(!sync.is_synced());
+        assert_eq!(send(&mut sync, "hearts,08"), "patch:4:2:left_card=hearts,08");
+        assert!(sync.ack(4));
+        assert!(sync.is_synced());
+    }
+
+    #[test]
+    fn bad_acks_are_reported() {
+        let mut sync = StateSync::new();
+        send(&mut sync, "hearts,05");
+        assert!(!sync.ack(99));
+        // after a resync the next message is a full state again
+        sync.ack(1);
+        sync.resync();
+        assert_eq!(send(&mut sync, "hearts,06"), "state:playing:alice:hearts,06:spades,K:2");
+    }
+
+    #[test]
+    fn too_many_unacked_states_sends_full_states() {
+        let mut sync = StateSync::new();
+        send(&mut sync, "hearts,05");
+        sync.ack(1);
+        for _ in 0..MAX_UNACKED_STATES {
+            assert!(send(&mut sync, "hearts,06").starts_with("patch:"));
+        }
+        assert!(send(&mut sync, "hearts,07").starts_with("state:playing:"));
+    }
+
+    #[test]
+    fn controlpads_that_cant_patch_get_full_states() {
+        let mut sync = StateSync::new();
+        let msg = sync.next_message(view("hearts,05"), false).encode(Encoding::Colon);
+        assert_eq!(msg, "state:playing:alice:hearts,05:spades,K");
+        assert!(sync.is_synced());
+    }
+
+    // ticks until the next resend (each resend is a new state)
+    fn ticks_until_resend(sync: &mut StateSync) -> u32 {
+        (1..).find(|_| sync.update().is_some()).unwrap()
+    }
+
+    #[test]
+    fn resends_back_off() {
+        let mut sync = StateSync::new();
+        send(&mut sync, "hearts,05");
+        assert_eq!(ticks_until_resend(&mut sync), RETRY_TICKS);
+        assert_eq!(ticks_until_resend(&mut sync), 2 * RETRY_TICKS);
+        assert_eq!(ticks_until_resend(&mut sync), 4 * RETRY_TICKS);
+        assert_eq!(ticks_until_resend(&mut sync), MAX_RETRY_TICKS);
+        assert_eq!(ticks_until_resend(&mut sync), MAX_RETRY_TICKS);
+        // an ack resets the backoff and there's nothing left to resend
+        let newest = sync.next_seq - 1;
+        assert!(sync.ack(newest));
+        assert!(sync.update().is_none());
+        send(&mut sync, "hearts,06");
+        assert_eq!(ticks_until_resend(&mut sync), RETRY_TICKS);
+    }
+}
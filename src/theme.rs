@@ -0,0 +1,148 @@
+use std::io::Read;
+
+use ggez::{graphics::Color, Context};
+use serde::Deserialize;
+
+// A theme is a card skin plus the table it's played on: where the card images
+// are, the table color and the font. The classic theme is built in; more can be
+// added as JSON manifests in resources/themes/ (the file name is the theme's
+// name), e.g. resources/themes/neon.json:
+// {
+//     "fronts": "/neon/card_{suit}_{rank}.png",
+//     "back": "/neon/card_back.png",
+//     "placeholder": "/neon/card_none.png",
+//     "table_color": [0.0, 0.0, 0.1, 1.0],
+//     "font": "/neon/font.ttf"
+// }
+// - paths are ggez resource paths (starting at resources/)
+// - anything left out is the same as the classic theme
+// - resources/themes/ comes with pixel (the classic card art on a dark table)
+//   and hires (for high-res card art in resources/hires/, any size works)
+
+const THEMES_DIR: &str = "/themes";
+const CLASSIC_THEME: &str = "classic";
+
+
+//////// Theme ////////
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    #[serde(skip)]
+    pub name: String,
+    // ({suit} and {rank} are replaced with e.g. "hearts" and "10")
+    pub fronts: String,
+    pub back: String,
+    pub placeholder: String,
+    pub table_color: [f32; 4],
+    // (None for ggez's built in font)
+    pub font: Option<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: CLASSIC_THEME.to_string(),
+            fronts: "/card_fronts/card_{suit}_{rank}.png".to_string(),
+            back: "/card_back.png".to_string(),
+            placeholder: "/card_none.png".to_string(),
+            table_color: [0.4, 0.9, 0.6, 1.0],
+            font: None,
+        }
+    }
+}
+
+impl Theme {
+    pub fn front_path(&self, suit: &str, rank: &str) -> String {
+        self.fronts.replace("{suit}", suit).replace("{rank}", rank)
+    }
+
+    pub fn table_color(&self) -> Color {
+        Color::from(self.table_color)
+    }
+
+    fn load(ctx: &Context, path: &str) -> Result<Self, String> {
+        let mut contents = String::new();
+        ctx.fs.open(path)
+            .and_then(|mut x| x.read_to_string(&mut contents).map_err(|e| e.into()))
+            .map_err(|e| format!("{}: {}", path, e))?;
+        Self::from_json(path, &contents)
+    }
+
+    // (a misspelled field is an error rather than silently left as classic)
+    fn from_json(path: &str, contents: &str) -> Result<Self, String> {
+        let mut theme: Theme = serde_json::from_str(contents).map_err(|e| format!("{}: {}", path, e))?;
+        theme.name = std::path::Path::new(path).file_stem()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(theme)
+    }
+}
+
+// the classic theme then every theme in resources/themes/ (sorted by name)
+pub fn load_themes(ctx: &Context) -> Vec<Theme> {
+    let mut themes = Vec::new();
+    // (no themes directory just means there's only the classic theme)
+    if let Ok(paths) = ctx.fs.read_dir(THEMES_DIR) {
+        for path in paths.filter(|x| x.extension().is_some_and(|x| x == "json")) {
+            // ggez resource paths always use /
+            let path = path.to_string_lossy().replace('\\', "/");
+            match Theme::load(ctx, &path) {
+                Ok(theme) if theme.name == CLASSIC_THEME => {
+                    println!("WARNING: {}: the classic theme is built in", path);
+                }
+                Ok(theme) => themes.push(theme),
+                Err(e) => println!("WARNING: couldn't load theme: {}", e),
+            }
+        }
+    }
+    themes.sort_by(|a, b| a.name.cmp(&b.name));
+    themes.insert(0, Theme::default());
+    themes
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn themes_are_named_after_their_files() {
+        let theme = Theme::from_json("/themes/neon.json", r#"{
+            "fronts": "/neon/{suit}-{rank}.png",
+            "back": "/neon/back.png",
+            "placeholder": "/neon/none.png",
+            "table_color": [0.0, 0.0, 0.1, 1.0],
+            "font": "/neon/font.ttf"
+        }"#).unwrap();
+        assert_eq!(theme.name, "neon");
+        assert_eq!(theme.front_path("hearts", "05"), "/neon/hearts-05.png");
+        assert_eq!(theme.table_color(), Color::new(0.0, 0.0, 0.1, 1.0));
+        assert_eq!(theme.font.as_deref(), Some("/neon/font.ttf"));
+    }
+
+    #[test]
+    fn missing_fields_are_classic() {
+        let theme = Theme::from_json("/themes/green.json", r#"{"table_color": [0.0, 1.0, 0.0, 1.0]}"#).unwrap();
+        let classic = Theme::default();
+        assert_eq!(theme, Theme { name: "green".to_string(), table_color: [0.0, 1.0, 0.0, 1.0], ..classic });
+        assert_eq!(Theme::from_json("/themes/classic2.json", "{}").unwrap().fronts, Theme::default().fronts);
+    }
+
+    #[test]
+    fn bad_themes_say_where_they_went_wrong() {
+        for contents in ["", "{", "42", r#"{"table_color": "green"}"#, r#"{"table_colour": [0, 1, 0, 1]}"#,
+                         r#"{"table_color": [0.0, 1.0]}"#] {
+            let e = Theme::from_json("/themes/bad.json", contents).unwrap_err();
+            assert!(e.starts_with("/themes/bad.json: "), "{}", e);
+        }
+    }
+
+    #[test]
+    fn shipped_themes_load() {
+        for (path, contents) in [("/themes/pixel.json", include_str!("../resources/themes/pixel.json")),
+                                 ("/themes/hires.json", include_str!("../resources/themes/hires.json"))] {
+            let theme = Theme::from_json(path, contents).unwrap();
+            assert_ne!(theme, Theme { name: theme.name.clone(), ..Theme::default() }, "{} is just classic", path);
+        }
+    }
+}
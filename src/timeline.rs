@@ -0,0 +1,295 @@
+use glam::Vec2;
+
+use crate::easing::*;
+use crate::progress::*;
+
+// Animations built out of small pieces: a tween animates one track (offset,
+// travel, scale, rotation, flip or opacity) from one value to another; delays wait;
+// sequences play their steps one after another and parallels play them all at
+// once; fire steps hand a value back (from update()) when they're reached,
+// which is how something finds out that (part of) an animation has finished.
+// - the timeline only says how things are transformed; what's being
+//   transformed, and where, is up to whatever draws it
+
+
+//////// Transform ////////
+// how an animated thing is drawn at some moment
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform {
+    // added to wherever the thing would be drawn
+    pub offset: Vec2,
+    // how far along its path the thing is (0.0 at the start, 1.0 at the end)
+    pub travel: f32,
+    pub scale: Vec2,
+    // (in radians)
+    pub rotation: f32,
+    // how far the thing has turned over (in radians, see card_flip.rs)
+    pub flip: f32,
+    pub opacity: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::ZERO,
+            travel: 0.0,
+            scale: Vec2::ONE,
+            rotation: 0.0,
+            flip: 0.0,
+            opacity: 1.0,
+        }
+    }
+}
+
+
+//////// Track ////////
+// the part of a Transform a tween animates (from, to)
+// - (the game doesn't animate every track yet)
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum Track {
+    Offset(Vec2, Vec2),
+    Travel(f32, f32),
+    Scale(Vec2, Vec2),
+    Rotation(f32, f32),
+    Flip(f32, f32),
+    Opacity(f32, f32),
+}
+
+impl Track {
+    fn apply(&self, transform: &mut Transform, p: f32) {
+        use Track::*;
+        match *self {
+            Offset(from, to) => transform.offset = from.lerp(to, p),
+            Travel(from, to) => transform.travel = lerp(from, to, p),
+            Scale(from, to) => transform.scale = from.lerp(to, p),
+            Rotation(from, to) => transform.rotation = lerp_rotation(from, to, p),
+            Flip(from, to) => transform.flip = lerp(from, to, p),
+            Opacity(from, to) => transform.opacity = lerp(from, to, p),
+        }
+    }
+}
+
+
+//////// Timeline ////////
+pub enum Timeline<T> {
+    Tween { track: Track, ease: Interpolation, progression: Progression },
+    Delay(Progression),
+    // (None once it's been fired)
+    Fire(Option<T>),
+    // the steps and which one is playing
+    Sequence(Vec<Timeline<T>>, usize),
+    Parallel(Vec<Timeline<T>>),
+}
+
+impl<T> Timeline<T> {
+    // (durations are in seconds)
+    pub fn tween(track: Track, duration: f32, ease: Interpolation) -> Self {
+        Timeline::Tween { track, ease, progression: Progression::new(duration) }
+    }
+
+    pub fn delay(duration: f32) -> Self {
+        Timeline::Delay(Progression::new(duration))
+    }
+
+    pub fn fire(value: T) -> Self {
+        Timeline::Fire(Some(value))
+    }
+
+    pub fn sequence(steps: Vec<Timeline<T>>) -> Self {
+        Timeline::Sequence(steps, 0)
+    }
+
+    pub fn parallel(steps: Vec<Timeline<T>>) -> Self {
+        Timeline::Parallel(steps)
+    }
+
+    // fire value once this timeline has finished
+    pub fn then_fire(self, value: T) -> Self {
+        Self::sequence(vec![self, Self::fire(value)])
+    }
+
+    // move the timeline along a tick; returns whatever was fired
+    pub fn update(&mut self) -> Vec<T> {
+        let mut fired = Vec::new();
+        self.advance(1.0, &mut fired);
+        fired
+    }
+
+    // move the timeline along `ticks` (which can include part of a tick),
+    // adding whatever's fired to `fired`; returns the ticks left over once
+    // it's done (0.0 if it isn't)
+    fn advance(&mut self, ticks: f32, fired: &mut Vec<T>) -> f32 {
+        use Timeline::*;
+        match self {
+            Tween { progression, .. } | Delay(progression) => progression.advance(ticks),
+            // (fires take no time)
+            Fire(value) => {
+                fired.extend(value.take());
+                ticks
+            }
+            // a step that finishes partway through a tick hands the rest of
+            // the tick to the step after it (so steps that finish straight
+            // away, like fires, don't hold up the steps after them)
+            Sequence(steps, current) => {
+                let mut ticks = ticks;
+                while let Some(step) = steps.get_mut(*current) {
+                    ticks = step.advance(ticks, fired);
+                    if !step.is_done() {
+                        break;
+                    }
+                    *current += 1;
+                }
+                ticks
+            }
+            // (what's left over after the longest step)
+            Parallel(steps) => {
+                let mut left_over = ticks;
+                for step in steps.iter_mut().filter(|x| !x.is_done()) {
+                    left_over = left_over.min(step.advance(ticks, fired));
+                }
+                left_over
+            }
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        use Timeline::*;
+        match self {
+            Tween { progression, .. } | Delay(progression) => progression.is_done(),
+            Fire(value) => value.is_none(),
+            Sequence(steps, current) => *current >= steps.len(),
+            Parallel(steps) => steps.iter().all(|x| x.is_done()),
+        }
+    }
+
+    // the transform right now (tracks that haven't been tweened yet are left
+    // at their defaults)
+    pub fn transform(&self) -> Transform {
+        let mut transform = Transform::default();
+        self.apply(&mut transform);
+        transform
+    }
+
+    fn apply(&self, transform: &mut Transform) {
+        use Timeline::*;
+        match self {
+            Tween { track, ease, progression } => {
+                track.apply(transform, ease.ease(progression.progress().min(1.0)));
+            }
+            Delay(_) | Fire(_) => (),
+            // (steps that are done keep their final values)
+            Sequence(steps, current) => {
+                for step in steps.iter().take(*current + 1) {
+                    step.apply(transform);
+                }
+            }
+            Parallel(steps) => {
+                for step in steps {
+                    step.apply(transform);
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (durations in ticks, since timelines move a tick at a time)
+    fn ticks(n: f32) -> f32 {
+        n / 60.0
+    }
+
+    fn travel(duration: f32) -> Timeline<u32> {
+        Timeline::tween(Track::Travel(0.0, 1.0), duration, Interpolation::Linear)
+    }
+
+    // the ticks on which each value was fired, until the timeline is done
+    fn play(timeline: &mut Timeline<u32>) -> Vec<(u32, u32)> {
+        let mut fired = Vec::new();
+        for tick in 1..1000 {
+            fired.extend(timeline.update().into_iter().map(|x| (x, tick)));
+            if timeline.is_done() {
+                return fired;
+            }
+        }
+        panic!("timeline never finished");
+    }
+
+    #[test]
+    fn sequences_play_in_order() {
+        let mut timeline = Timeline::sequence(vec![
+            Timeline::fire(1),
+            Timeline::tween(Track::Offset(Vec2::ZERO, Vec2::new(10.0, 0.0)), ticks(30.0), Interpolation::Linear),
+            Timeline::fire(2),
+            Timeline::delay(ticks(15.0)),
+            Timeline::fire(3),
+        ]);
+        timeline.update();
+        assert_eq!(timeline.transform().offset, Vec2::new(10.0 / 30.0, 0.0));
+        assert_eq!(play(&mut timeline), [(2, 29), (3, 44)]);
+        // (steps that are done keep their final values)
+        assert_eq!(timeline.transform().offset, Vec2::new(10.0, 0.0));
+    }
+
+    #[test]
+    fn sequences_carry_leftover_ticks() {
+        // each step takes a tick and a half, so both are done after 3 ticks
+        // (not 4)
+        let mut timeline = Timeline::sequence(vec![
+            Timeline::delay(ticks(1.5)),
+            travel(ticks(1.5)),
+        ]).then_fire(1);
+        timeline.update();
+        assert!(timeline.transform().travel == 0.0);
+        // (half of the second tick is left for the tween)
+        timeline.update();
+        assert!((timeline.transform().travel - 1.0 / 3.0).abs() < 0.001);
+        assert_eq!(timeline.update(), [1]);
+        assert!(timeline.is_done());
+        // a step that finishes right at the end of a tick leaves nothing over
+        let mut timeline = Timeline::sequence(vec![travel(ticks(2.0)), travel(ticks(2.0))]).then_fire(1);
+        assert_eq!(play(&mut timeline), [(1, 4)]);
+    }
+
+    #[test]
+    fn parallels_finish_with_their_longest_step() {
+        let mut timeline = Timeline::parallel(vec![
+            Timeline::delay(ticks(6.0)).then_fire(1),
+            travel(ticks(30.0)).then_fire(2),
+            Timeline::delay(ticks(15.0)).then_fire(3),
+        ]).then_fire(4);
+        assert_eq!(play(&mut timeline), [(1, 6), (3, 15), (2, 30), (4, 30)]);
+        assert!(timeline.transform().travel == 1.0);
+    }
+
+    #[test]
+    fn fires_fire_once() {
+        let mut timeline = Timeline::fire(1);
+        assert!(!timeline.is_done());
+        assert_eq!(timeline.update(), [1]);
+        assert!(timeline.is_done());
+        assert!(timeline.update().is_empty());
+        // (including in parallels, which keep updating until everything's done)
+        let mut timeline = Timeline::parallel(vec![Timeline::fire(1), travel(ticks(3.0))]);
+        assert_eq!(play(&mut timeline), [(1, 1)]);
+        assert!(timeline.update().is_empty());
+    }
+
+    #[test]
+    fn zero_length_delays_take_no_time() {
+        let mut timeline = Timeline::delay(0.0).then_fire(1);
+        assert_eq!(timeline.update(), [1]);
+        assert!(timeline.is_done());
+        let mut timeline = Timeline::sequence(vec![
+            Timeline::delay(0.0),
+            travel(ticks(1.0)),
+            Timeline::delay(0.0),
+        ]).then_fire(1);
+        assert_eq!(timeline.update(), [1]);
+        assert!(timeline.transform().travel == 1.0);
+    }
+}
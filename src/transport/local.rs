@@ -0,0 +1,90 @@
+use std::sync::{Mutex, MutexGuard};
+
+// Local clients are controlpads that live inside the game process (e.g. bots).
+// They show up to the game like any other controlpad, but their messages go
+// through these mailboxes instead of a backend.
+
+
+//////// LocalClients ////////
+struct LocalClient {
+    handle: String,
+    // messages from the client to the game
+    to_game: Vec<String>,
+    // messages from the game to the client
+    to_client: Vec<String>,
+}
+
+struct LocalClients {
+    clients: Vec<LocalClient>,
+    changed: bool,
+}
+
+static LOCAL_CLIENTS: Mutex<LocalClients> = Mutex::new(LocalClients {
+    clients: Vec::new(),
+    changed: false,
+});
+
+fn local_clients() -> MutexGuard<'static, LocalClients> {
+    LOCAL_CLIENTS.lock().unwrap()
+}
+
+impl LocalClients {
+    fn get_mut(&mut self, handle: &str) -> Option<&mut LocalClient> {
+        self.clients.iter_mut().find(|x| x.handle == handle)
+    }
+}
+
+
+//////// Used by the transport ////////
+pub fn is_local(handle: &str) -> bool {
+    local_clients().clients.iter().any(|x| x.handle == handle)
+}
+
+pub fn take_changed() -> bool {
+    let mut local = local_clients();
+    let changed = local.changed;
+    local.changed = false;
+    changed
+}
+
+pub fn handles() -> Vec<String> {
+    local_clients().clients.iter().map(|x| x.handle.clone()).collect()
+}
+
+pub fn messages_to_game(handle: &str) -> Vec<String> {
+    local_clients().get_mut(handle).map_or(Vec::new(), |x| x.to_game.drain(..).collect())
+}
+
+pub fn message_to_client(handle: &str, msg: &str) {
+    if let Some(client) = local_clients().get_mut(handle) {
+        client.to_client.push(msg.to_string());
+    }
+}
+
+
+//////// Used by local clients ////////
+pub fn connect(handle: &str) {
+    let mut local = local_clients();
+    local.clients.push(LocalClient {
+        handle: handle.to_string(),
+        to_game: Vec::new(),
+        to_client: Vec::new(),
+    });
+    local.changed = true;
+}
+
+pub fn disconnect(handle: &str) {
+    let mut local = local_clients();
+    local.clients.retain(|x| x.handle != handle);
+    local.changed = true;
+}
+
+pub fn send(handle: &str, msg: &str) {
+    if let Some(client) = local_clients().get_mut(handle) {
+        client.to_game.push(msg.to_string());
+    }
+}
+
+pub fn receive(handle: &str) -> Vec<String> {
+    local_clients().get_mut(handle).map_or(Vec::new(), |x| x.to_client.drain(..).collect())
+}
@@ -0,0 +1,68 @@
+// The game talks to controlpads through one of two backends, picked with cargo
+// features:
+// - ipc-server: the ControlpadServer crate, which talks (via file io) to the
+//   controlpad server and node web server started by controlpad_server/start.sh
+//   (only in ipc/Cargo.toml, since the crate's in a private repo)
+// - native-server: a websocket server and static file server built into this
+//   binary (no node or controlpad server needed, the default in Cargo.toml)
+// Either way the rest of the game only uses the functions in this module.
+// Local clients (see local.rs) are mixed in with the backend's controlpads.
+
+pub mod local;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "native-server")]
+mod native_server;
+#[cfg(feature = "native-server")]
+use native_server as backend;
+#[cfg(all(feature = "ipc-server", not(feature = "native-server")))]
+use controlpads as backend;
+
+#[cfg(not(any(feature = "ipc-server", feature = "native-server")))]
+compile_error!("enable the ipc-server or native-server feature to talk to controlpads");
+
+pub type TransportError = String;
+
+// while replaying a recorded game the controlpads aren't really there, so
+// messages to them are dropped (and the backend is never started)
+static REPLAYING: AtomicBool = AtomicBool::new(false);
+
+pub fn set_replaying(replaying: bool) {
+    REPLAYING.store(replaying, Ordering::Relaxed);
+}
+
+// NOTE: handles are passed to the backends as &String (rather than &str)
+// because that's what the ControlpadServer crate's functions take
+
+// whether controlpads have connected or disconnected since the last call
+pub fn clients_changed() -> Result<bool, TransportError> {
+    let local_changed = local::take_changed();
+    let changed = backend::clients_changed().map_err(|e| e.to_string())?;
+    Ok(changed || local_changed)
+}
+
+pub fn get_client_handles() -> Result<Vec<String>, TransportError> {
+    let mut handles = backend::get_client_handles().map_err(|e| e.to_string())?;
+    handles.extend(local::handles());
+    Ok(handles)
+}
+
+// messages received from a controlpad since the last call
+pub fn get_messages(handle: &str) -> Result<Vec<String>, TransportError> {
+    if local::is_local(handle) {
+        return Ok(local::messages_to_game(handle));
+    }
+    backend::get_messages(&handle.to_string()).map_err(|e| e.to_string())
+}
+
+pub fn send_message(handle: &str, msg: &str) -> Result<(), TransportError> {
+    if REPLAYING.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    if local::is_local(handle) {
+        local::message_to_client(handle, msg);
+        return Ok(());
+    }
+    backend::send_message(&handle.to_string(), msg).map_err(|e| e.to_string())
+}
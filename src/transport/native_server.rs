@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use tungstenite::{Message, WebSocket};
+
+// the same ports the node web server and controlpad server use, so that
+// controller/ works unchanged
+const WEB_PORT: u16 = 3000;
+const WEBSOCKET_PORT: u16 = 50079;
+// served over http (relative to where the game is run from)
+const CONTROLLER_DIR: &str = "./controller";
+// how long a websocket thread waits for a message before checking whether
+// the game has anything to send
+const POLL_TIME: Duration = Duration::from_millis(5);
+
+
+//////// ServerState ////////
+// shared between the game thread and the websocket threads
+struct Client {
+    // identifies this particular connection so a stale connection can't
+    // remove a client that has since reconnected
+    connection_id: u64,
+    inbox: Vec<String>,
+    outbox: Sender<String>,
+}
+
+struct ServerState {
+    // in the order they connected
+    handles: Vec<String>,
+    clients: HashMap<String, Client>,
+    changed: bool,
+    next_connection_id: u64,
+}
+
+static SERVER: OnceLock<Mutex<ServerState>> = OnceLock::new();
+
+// the servers are started the first time the game asks about controlpads
+fn server() -> MutexGuard<'static, ServerState> {
+    SERVER.get_or_init(|| {
+        start_web_server();
+        start_websocket_server();
+        Mutex::new(ServerState {
+            handles: Vec::new(),
+            clients: HashMap::new(),
+            changed: false,
+            next_connection_id: 0,
+        })
+    }).lock().unwrap()
+}
+
+
+//////// Backend Interface ////////
+// (the same functions the ControlpadServer crate provides)
+pub fn clients_changed() -> Result<bool, String> {
+    let mut state = server();
+    let changed = state.changed;
+    state.changed = false;
+    Ok(changed)
+}
+
+pub fn get_client_handles() -> Result<Vec<String>, String> {
+    Ok(server().handles.clone())
+}
+
+pub fn get_messages(handle: &String) -> Result<Vec<String>, String> {
+    let mut state = server();
+    let client = state.clients.get_mut(handle)
+        .ok_or(format!("no controlpad with handle {}", handle))?;
+    Ok(client.inbox.drain(..).collect())
+}
+
+pub fn send_message(handle: &String, msg: &str) -> Result<(), String> {
+    let state = server();
+    let client = state.clients.get(handle)
+        .ok_or(format!("no controlpad with handle {}", handle))?;
+    client.outbox.send(msg.to_string()).map_err(|e| e.to_string())
+}
+
+
+//////// Websocket Server ////////
+fn start_websocket_server() {
+    let listener = TcpListener::bind(("0.0.0.0", WEBSOCKET_PORT))
+        .unwrap_or_else(|e| panic!("failed to listen on port {}: {}", WEBSOCKET_PORT, e));
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || {
+                if let Err(e) = run_connection(stream) {
+                    println!("WARNING: controlpad connection error: {}", e);
+                }
+            });
+        }
+    });
+}
+
+fn run_connection(stream: TcpStream) -> Result<(), String> {
+    let ip = stream.peer_addr().map_err(|e| e.to_string())?.ip();
+    let mut socket = tungstenite::accept(stream).map_err(|e| e.to_string())?;
+    // the first thing controlpad.js sends is a single byte "sub ID" that lets
+    // several tabs on one device be treated as separate controlpads
+    let subid = loop {
+        match socket.read().map_err(|e| e.to_string())? {
+            Message::Binary(bytes) => break bytes.first().copied().unwrap_or(0),
+            Message::Close(_) => return Ok(()),
+            _ => continue,
+        }
+    };
+    let handle = format!("{}-{}", ip, subid);
+    let (outbox, outgoing) = channel();
+    let connection_id = add_client(&handle, outbox);
+    socket.get_mut().set_read_timeout(Some(POLL_TIME)).map_err(|e| e.to_string())?;
+    let result = pump_messages(&mut socket, &handle, outgoing);
+    remove_client(&handle, connection_id);
+    result
+}
+
+fn add_client(handle: &str, outbox: Sender<String>) -> u64 {
+    let mut state = server();
+    let connection_id = state.next_connection_id;
+    state.next_connection_id += 1;
+    // a reconnecting controlpad replaces its old connection
+    let client = Client { connection_id, inbox: Vec::new(), outbox };
+    if state.clients.insert(handle.to_string(), client).is_none() {
+        state.handles.push(handle.to_string());
+    }
+    state.changed = true;
+    connection_id
+}
+
+fn remove_client(handle: &str, connection_id: u64) {
+    let mut state = server();
+    if state.clients.get(handle).map(|c| c.connection_id) != Some(connection_id) {
+        return;
+    }
+    state.clients.remove(handle);
+    state.handles.retain(|h| h != handle);
+    state.changed = true;
+}
+
+// shuttle messages between the socket and the game until the socket closes
+fn pump_messages(socket: &mut WebSocket<TcpStream>, handle: &str,
+                 outgoing: Receiver<String>) -> Result<(), String> {
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                if let Some(client) = server().clients.get_mut(handle) {
+                    client.inbox.push(text);
+                }
+            }
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => (),
+            Err(tungstenite::Error::Io(e))
+                if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => (),
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(e.to_string()),
+        }
+        for msg in outgoing.try_iter() {
+            socket.send(Message::Text(msg)).map_err(|e| e.to_string())?;
+        }
+    }
+}
+
+
+//////// Web Server ////////
+// serves the files in controller/ (just enough http for a browser to load them)
+fn start_web_server() {
+    let listener = TcpListener::bind(("0.0.0.0", WEB_PORT))
+        .unwrap_or_else(|e| panic!("failed to listen on port {}: {}", WEB_PORT, e));
+    println!("serving {} on port {}", CONTROLLER_DIR, WEB_PORT);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || {
+                if let Err(e) = serve_file(stream) {
+                    println!("WARNING: web server error: {}", e);
+                }
+            });
+        }
+    });
+}
+
+fn serve_file(mut stream: TcpStream) -> Result<(), String> {
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    // e.g. "GET /app.js?subid=1 HTTP/1.1"
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).map_err(|e| e.to_string())?;
+    // skip the headers
+    loop {
+        let mut header = String::new();
+        let n = reader.read_line(&mut header).map_err(|e| e.to_string())?;
+        if n == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+    let url = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let url_path = url.split('?').next().unwrap_or("/").trim_start_matches('/');
+    let url_path = if url_path.is_empty() { "index.html" } else { url_path };
+    let response = match controller_file(url_path) {
+        Some(body) => {
+            let header = format!("HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+                                  Connection: close\r\n\r\n", content_type(url_path), body.len());
+            [header.into_bytes(), body].concat()
+        }
+        None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+    };
+    stream.write_all(&response).map_err(|e| e.to_string())
+}
+
+// only files inside CONTROLLER_DIR can be served
+fn controller_file(url_path: &str) -> Option<Vec<u8>> {
+    let path = Path::new(url_path);
+    if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    fs::read(Path::new(CONTROLLER_DIR).join(path)).ok()
+}
+
+fn content_type(url_path: &str) -> &'static str {
+    match Path::new(url_path).extension().and_then(|x| x.to_str()) {
+        Some("html") => "text/html",
+        Some("js") => "text/javascript",
+        Some("css") => "text/css",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}